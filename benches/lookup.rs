@@ -0,0 +1,72 @@
+//!Label lookups through interned slots, compared to `Template::content`.
+//!
+//!Run with `cargo bench`. The `string` cases use the `HashMap<String, ContentType>`
+//!in `Template::content`, while the `slot` cases use `Label`s resolved once
+//!from the `CompiledTemplate` and a `Context` created for it.
+
+extern crate fragments;
+
+use fragments::{Template, CompiledTemplate, Context, Lookup, Label};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use timing::{measure, report};
+
+mod timing;
+
+const LABELS: usize = 50;
+
+fn main() {
+	let mut source = String::new();
+	for i in 0..LABELS {
+		writeln!(&mut source, "[[:label{}]] [[?cond{}]]on[[/]]", i, i).unwrap();
+	}
+
+	let mut compiled: CompiledTemplate = source.parse().unwrap();
+	compiled.compile();
+	let compiled = Arc::new(compiled);
+
+	let names: Vec<String> = (0..LABELS).map(|i| format!("label{}", i)).collect();
+	let labels: Vec<Label> = names.iter().map(|name| compiled.label(name).unwrap()).collect();
+
+	let mut template = Template::from_compiled(compiled.clone());
+	let mut context = Context::for_template(&compiled);
+
+	println!("insert:");
+	report("string", measure(|| {
+		for (i, name) in names.iter().enumerate() {
+			template.insert(name.clone(), i);
+		}
+	}), 0);
+	report("slot", measure(|| {
+		for (i, &label) in labels.iter().enumerate() {
+			context.insert_at(label, i);
+		}
+	}), 0);
+
+	println!("get:");
+	report("string", measure(|| {
+		for name in names.iter() {
+			assert!(template.get_content(name).is_some());
+		}
+	}), 0);
+	report("slot", measure(|| {
+		for &label in labels.iter() {
+			assert!(context.get_at(label).is_some());
+		}
+	}), 0);
+
+	let mut output = String::new();
+
+	println!("render:");
+	let string_time = measure(|| {
+		output.clear();
+		write!(&mut output, "{}", template).unwrap();
+	});
+	let bytes = output.len();
+	report("string", string_time, bytes);
+	report("slot", measure(|| {
+		output.clear();
+		write!(&mut output, "{}", compiled.render(&context)).unwrap();
+	}), bytes);
+}
@@ -0,0 +1,100 @@
+//!Render throughput of interpreted and compiled templates.
+//!
+//!Run with `cargo bench`. Each case is rendered into a reused `String` and
+//!the time per render and the output throughput are reported for both the
+//!token interpreter and the compiled `Program`.
+
+extern crate fragments;
+
+use fragments::{Template, Spanned};
+use std::borrow::ToOwned;
+use std::fmt;
+use std::fmt::Write;
+
+use timing::{measure, report};
+
+mod timing;
+
+fn main() {
+	bench_case("placeholders", setup_placeholders);
+	bench_case("repeated labels", setup_repeated);
+	bench_case("nested conditions", setup_conditions);
+	bench_case("generators", setup_generators);
+}
+
+fn bench_case<F: Fn(&mut Template<'static>)>(name: &str, setup: F) {
+	let source = source_for(name);
+
+	let mut interpreted: Template = source.parse().unwrap();
+	setup(&mut interpreted);
+
+	let mut compiled: Template = source.parse().unwrap();
+	setup(&mut compiled);
+	compiled.compile();
+
+	assert_eq!(interpreted.to_string(), compiled.to_string());
+
+	let mut output = String::new();
+	let interpreted_time = measure(|| render(&interpreted, &mut output));
+	let compiled_time = measure(|| render(&compiled, &mut output));
+	let bytes = output.len();
+
+	println!("{}:", name);
+	report("interpreted", interpreted_time, bytes);
+	report("compiled", compiled_time, bytes);
+}
+
+fn source_for(name: &str) -> String {
+	let mut source = String::new();
+
+	match name {
+		"placeholders" => for i in 0..50 {
+			writeln!(&mut source, "Item {}: [[:item{}]]", i, i).unwrap();
+		},
+		"repeated labels" => for _ in 0..50 {
+			source.push_str("[[:first]] [[:last]] <[[:email]]>\n");
+		},
+		"nested conditions" => for i in 0..20 {
+			writeln!(&mut source, "[[?a]]a[[?!b]]b[[?:c{}]]c: [[:c{}]][[/]][[/]][[/]]", i % 5, i % 5).unwrap();
+		},
+		"generators" => for _ in 0..50 {
+			source.push_str("[[+join a b c]]\n");
+		},
+		_ => unreachable!()
+	}
+
+	source
+}
+
+fn setup_placeholders(template: &mut Template<'static>) {
+	for i in 0..50 {
+		template.insert(format!("item{}", i), i);
+	}
+}
+
+fn setup_repeated(template: &mut Template<'static>) {
+	template.insert("first".to_owned(), "Peter");
+	template.insert("last".to_owned(), "Parker");
+	template.insert("email".to_owned(), "peter@example.com");
+}
+
+fn setup_conditions(template: &mut Template<'static>) {
+	template.set("a".to_owned(), true);
+	for i in 0..5 {
+		template.insert(format!("c{}", i), i);
+	}
+}
+
+fn setup_generators(template: &mut Template<'static>) {
+	template.insert_generator("join".to_owned(), |parts: &[Spanned<String>], f: &mut fmt::Formatter| {
+		for part in parts {
+			f.write_str(part)?;
+		}
+		Ok(())
+	});
+}
+
+fn render(template: &Template, output: &mut String) {
+	output.clear();
+	write!(output, "{}", template).unwrap();
+}
@@ -0,0 +1,31 @@
+//!Shared timing helpers for the benchmarks.
+
+use std::time::{Duration, Instant};
+
+const MIN_DURATION_MS: u64 = 500;
+
+///Run `f` repeatedly for at least half a second and return the average time per run.
+pub fn measure<F: FnMut()>(mut f: F) -> Duration {
+	let min_duration = Duration::from_millis(MIN_DURATION_MS);
+	let mut iterations = 0u32;
+	let start = Instant::now();
+
+	while start.elapsed() < min_duration {
+		f();
+		iterations += 1;
+	}
+
+	start.elapsed() / iterations
+}
+
+///Print the time per run and, if `bytes` is non-zero, the throughput.
+pub fn report(name: &str, time: Duration, bytes: usize) {
+	let nanos = time.as_secs() * 1_000_000_000 + time.subsec_nanos() as u64;
+
+	if bytes > 0 {
+		let throughput = if nanos > 0 { bytes as f64 * 1000.0 / nanos as f64 } else { 0.0 };
+		println!("    {:12} {:>10} ns/iter {:>10.1} MB/s", name, nanos, throughput);
+	} else {
+		println!("    {:12} {:>10} ns/iter", name, nanos);
+	}
+}
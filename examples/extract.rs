@@ -0,0 +1,57 @@
+extern crate fragments;
+use fragments::{Extractor, Catalog};
+use std::env;
+use std::process;
+
+//Extract the translation tokens of templates into a `.pot` catalog:
+//
+//    extract [--strings] [--catalog de.po]... templates/*.html > messages.pot
+//
+//`--strings` extracts literal text as well, and the entries of each
+//`--catalog` that aren't used by any of the templates are listed as stale.
+fn main() {
+	let mut extractor = Extractor::new();
+	let mut templates = Vec::new();
+	let mut catalogs = Vec::new();
+	let mut args = env::args().skip(1);
+
+	while let Some(arg) = args.next() {
+		match &*arg {
+			"--strings" => extractor.set_strings(true),
+			"--catalog" => match args.next() {
+				Some(path) => catalogs.push(path),
+				None => fail("expected a catalog after '--catalog'")
+			},
+			_ => templates.push(arg)
+		}
+	}
+
+	for path in templates.iter() {
+		if let Err(e) = extractor.add_file(path) {
+			fail(&e);
+		}
+	}
+
+	//The catalog is written to stdout, and stale entries to stderr
+	print!("{}", extractor);
+
+	let mut stale = false;
+
+	for path in catalogs.iter() {
+		let catalog = Catalog::from_file(path).unwrap_or_else(|e| fail(&e));
+
+		for id in extractor.stale(&catalog) {
+			eprintln!("{}: stale entry '{}'", path, id);
+			stale = true;
+		}
+	}
+
+	if stale {
+		process::exit(1);
+	}
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("error: {}", message);
+	process::exit(2);
+}
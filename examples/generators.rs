@@ -1,5 +1,5 @@
 extern crate fragments;
-use fragments::Template;
+use fragments::{Template, Spanned};
 use std::borrow::ToOwned;
 use std::fmt;
 
@@ -11,12 +11,12 @@ fn main() {
 	template.insert("name".to_owned(), "Peter");
 
 	//Closures and functions with the signature
-    //`fn(&[String], &mut fmt::Formatter) -> fmt::Result`
+    //`fn(&[Spanned<String>], &mut fmt::Formatter) -> fmt::Result`
     //will automatically implement the `Generator` trait.
     //This generator will just concatenate the arguments.
     //I expect you to make cooler generators, yourself ;)
 	template.insert_generator("join".to_owned(),
-        |parts: &[String], f: &mut fmt::Formatter| {
+        |parts: &[Spanned<String>], f: &mut fmt::Formatter| {
             fmt::Display::fmt(&parts.concat(), f)
         }
     );
@@ -8,10 +8,10 @@ use std::path::Path;
 fn main() {
 	//Load the content of a file into a Template
 	//The file contains the text 'Hello, [[:name]]!', in this example
-	let file = File::open(&Path::new("path/to/my/template.txt")).unwrap();
+	let file = File::open(Path::new("path/to/my/template.txt")).unwrap();
 	let mut template = match Template::from_buffer(&mut BufReader::new(file)) {
 		Ok(template) => template,
-		Err(e) => panic!(e)
+		Err(e) => panic!("{}", e)
 	};
 
 	//Insert something into the `name` placeholder
@@ -0,0 +1,191 @@
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{DeriveInput, Data, Fields, LitStr};
+
+use std::path::PathBuf;
+
+use fragments_syntax::{Spanned, Token};
+
+///A field that provides content or conditions for a label.
+struct Field {
+	ident: syn::Ident,
+	label: String
+}
+
+///The template that the struct is checked against.
+enum Source {
+	Path(LitStr),
+	Inline(LitStr)
+}
+
+pub fn derive(input: &DeriveInput) -> TokenStream2 {
+	match try_derive(input) {
+		Ok(tokens) => tokens,
+		Err(error) => error
+	}
+}
+
+fn try_derive(input: &DeriveInput) -> Result<TokenStream2, TokenStream2> {
+	let fields = match input.data {
+		Data::Struct(ref data) => match data.fields {
+			Fields::Named(ref fields) => &fields.named,
+			_ => return Err(error(input, "TemplateContext can only be derived for structs with named fields"))
+		},
+		_ => return Err(error(input, "TemplateContext can only be derived for structs"))
+	};
+
+	let source = template_source(input)?;
+
+	let mut context_fields = Vec::new();
+	for field in fields.iter() {
+		let ident = field.ident.clone().expect("named fields have names");
+		let mut label = ident.to_string();
+		let mut skip = false;
+
+		for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("template")) {
+			let result = attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("rename") {
+					let value: LitStr = meta.value()?.parse()?;
+					label = value.value();
+					Ok(())
+				} else if meta.path.is_ident("skip") {
+					skip = true;
+					Ok(())
+				} else {
+					Err(meta.error("expected `rename` or `skip`"))
+				}
+			});
+
+			result.map_err(super::syn_error)?;
+		}
+
+		if !skip {
+			context_fields.push(Field {
+				ident,
+				label
+			});
+		}
+	}
+
+	let dependency = match source {
+		Some(ref source) => check(input, source, &context_fields)?,
+		None => None
+	};
+
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	let fills = context_fields.iter().map(|field| {
+		let ident = &field.ident;
+		let label = &field.label;
+		quote!(::fragments::TemplateField::fill_field(&self.#ident, &format!("{}{}", prefix, #label), target);)
+	});
+
+	Ok(quote!(
+		impl #impl_generics ::fragments::TemplateContext for #name #ty_generics #where_clause {
+			fn fill_context<'c, F: ::fragments::Fill<'c>>(&'c self, prefix: &str, target: &mut F) {
+				#dependency
+				#(#fills)*
+			}
+		}
+
+		impl #impl_generics ::fragments::TemplateField for #name #ty_generics #where_clause {
+			fn fill_field<'c, F: ::fragments::Fill<'c>>(&'c self, label: &str, target: &mut F) {
+				::fragments::TemplateContext::fill_context(self, &format!("{}.", label), target)
+			}
+		}
+	))
+}
+
+///Find the `path` or `source` of the template to check against, if any.
+fn template_source(input: &DeriveInput) -> Result<Option<Source>, TokenStream2> {
+	let mut source = None;
+
+	for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("template")) {
+		let result = attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("path") {
+				source = Some(Source::Path(meta.value()?.parse()?));
+				Ok(())
+			} else if meta.path.is_ident("source") {
+				source = Some(Source::Inline(meta.value()?.parse()?));
+				Ok(())
+			} else {
+				Err(meta.error("expected `path` or `source`"))
+			}
+		});
+
+		result.map_err(super::syn_error)?;
+	}
+
+	Ok(source)
+}
+
+///Check that every label in the template is provided by a field. Returns
+///code that makes the compiler track the template file, if there is one.
+fn check(input: &DeriveInput, source: &Source, fields: &[Field]) -> Result<Option<TokenStream2>, TokenStream2> {
+	let (tokens, path): (Vec<Spanned<Token>>, Option<PathBuf>) = match *source {
+		Source::Path(ref literal) => {
+			let (source, path) = super::read(literal)?;
+			(super::parse(&source, literal, Some(&path))?, Some(path))
+		},
+		Source::Inline(ref literal) => (super::parse(&literal.value(), literal, None)?, None)
+	};
+
+	let mut labels = Vec::new();
+	collect_labels(&tokens, &mut labels);
+
+	let missing: Vec<String> = labels.into_iter()
+		.filter(|label| !fields.iter().any(|field| covers(&field.label, label)))
+		.collect();
+
+	if missing.is_empty() {
+		Ok(path.map(|path| super::dependency(&path)))
+	} else {
+		let message = format!("`{}` has no fields for the labels {}", input.ident, missing.iter()
+			.map(|label| format!("`{}`", label))
+			.collect::<Vec<_>>()
+			.join(", ")
+		);
+		Err(error(input, &message))
+	}
+}
+
+///A field covers its own label, and labels that start with its label and a
+///`.`, since those are expected to be provided by a nested struct.
+fn covers(field: &str, label: &str) -> bool {
+	label == field || (label.starts_with(field) && label[field.len()..].starts_with('.'))
+}
+
+///Collect the distinct placeholder and condition labels, in the order they first appear.
+fn collect_labels(tokens: &[Spanned<Token>], labels: &mut Vec<String>) {
+	for token in tokens.iter() {
+		let label = match token.node {
+			Token::Placeholder(ref k) | Token::Filtered(ref k, _) => k,
+			Token::Conditional(ref k, _, ref tokens) | Token::ContentConditional(ref k, _, ref tokens) => {
+				if !labels.contains(k) {
+					labels.push(k.clone());
+				}
+
+				collect_labels(tokens, labels);
+				continue;
+			},
+			Token::Translated(_, ref args) => {
+				for label in args.iter().filter_map(|arg| arg.label()) {
+					if !labels.iter().any(|l| l == label) {
+						labels.push(label.to_owned());
+					}
+				}
+
+				continue;
+			},
+			Token::String(_) | Token::Generated(..) | Token::Include(_) => continue
+		};
+
+		if !labels.contains(label) {
+			labels.push(label.clone());
+		}
+	}
+}
+
+fn error(input: &DeriveInput, message: &str) -> TokenStream2 {
+	let span = input.ident.span();
+	quote_spanned!(span=> compile_error!(#message);)
+}
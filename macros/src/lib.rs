@@ -0,0 +1,220 @@
+//!Procedural macros for parsing templates and checking contexts at compile time.
+//!
+//!The macros are re-exported by `fragments`, and should be used from there.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+extern crate fragments_syntax;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::LitStr;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+mod context;
+
+use fragments_syntax::{parser, Span, Spanned, Token, ArgumentValue};
+
+///Parse a template at compile time.
+///
+///```ignore
+///let template = template!("Hello, [[:name]]!");
+///```
+///
+///The result is a shared `Arc<CompiledTemplate>`, and a syntax error in
+///the template becomes a compile error.
+///
+///The template is parsed at compile time, but the token tree owns its
+///strings and lists, so it can't be a `static`. The macro expands to code
+///that builds the tree and the `CompiledTemplate` the first time it's
+///evaluated, and stores them in a `OnceLock`. Later evaluations only clone
+///the `Arc`.
+#[proc_macro]
+pub fn template(input: TokenStream) -> TokenStream {
+	let literal: LitStr = match syn::parse(input) {
+		Ok(literal) => literal,
+		Err(e) => return syn_error(e).into()
+	};
+
+	expand(&literal.value(), &literal, None).into()
+}
+
+///Read and parse a template file at compile time.
+///
+///```ignore
+///let template = include_template!("templates/page.html");
+///```
+///
+///The path is relative to the directory of the crate's `Cargo.toml`. The
+///result is a shared `Arc<CompiledTemplate>`, and a syntax error in the
+///template becomes a compile error. The crate is rebuilt when the file
+///changes. The template is escaped as described by `Escaping::for_extension`.
+///
+///Like with `template!`, the result isn't static data. The token tree is
+///built the first time the expression is evaluated, and then shared by
+///later evaluations.
+#[proc_macro]
+pub fn include_template(input: TokenStream) -> TokenStream {
+	let literal: LitStr = match syn::parse(input) {
+		Ok(literal) => literal,
+		Err(e) => return syn_error(e).into()
+	};
+
+	match read(&literal) {
+		Ok((source, path)) => expand(&source, &literal, Some(path)).into(),
+		Err(error) => error.into()
+	}
+}
+
+///Implement `TemplateContext` and `TemplateField` for a struct.
+///
+///See the documentation for `TemplateContext` in `fragments` for the details.
+#[proc_macro_derive(TemplateContext, attributes(template))]
+pub fn derive_template_context(input: TokenStream) -> TokenStream {
+	match syn::parse(input) {
+		Ok(input) => context::derive(&input).into(),
+		Err(e) => syn_error(e).into()
+	}
+}
+
+fn expand(source: &str, literal: &LitStr, path: Option<PathBuf>) -> TokenStream2 {
+	let tokens = match parse(source, literal, path.as_deref()) {
+		Ok(tokens) => tokens,
+		Err(error) => return error
+	};
+
+	let tokens = tokens.iter().map(spanned_token);
+	let extension = path.as_ref()
+		.and_then(|path| path.extension())
+		.map(|extension| extension.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	let dependency = path.map(|path| dependency(&path));
+
+	//The tokens own their strings, so the tree is built on first use
+	quote!({
+		#dependency
+		static TEMPLATE: ::std::sync::OnceLock<::std::sync::Arc<::fragments::CompiledTemplate>> = ::std::sync::OnceLock::new();
+		TEMPLATE.get_or_init(|| {
+			let mut template = ::fragments::CompiledTemplate::from_tokens(vec![#(#tokens),*]);
+			template.set_escaping(::fragments::Escaping::for_extension(#extension));
+			::std::sync::Arc::new(template)
+		}).clone()
+	})
+}
+
+///Read a template file, where `literal` is the path relative to the crate's `Cargo.toml`.
+fn read(literal: &LitStr) -> Result<(String, PathBuf), TokenStream2> {
+	let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_owned());
+	let path = Path::new(&root).join(literal.value());
+
+	let mut source = String::new();
+	let read = File::open(&path).and_then(|mut file| file.read_to_string(&mut source));
+
+	match read {
+		Ok(_) => Ok((source, path)),
+		Err(e) => {
+			let message = format!("couldn't read {}: {}", path.display(), e);
+			Err(error(literal, &message))
+		}
+	}
+}
+
+///Parse a template, or create a compile error with the location of the syntax error.
+fn parse(source: &str, literal: &LitStr, path: Option<&Path>) -> Result<Vec<Spanned<Token>>, TokenStream2> {
+	parser::parse(source.chars().map(Ok)).map_err(|e| {
+		let (line, column) = line_and_column(source, e.span.start);
+		let message = match path {
+			Some(path) => format!("{} at {}:{}:{}", e.node, path.display(), line, column),
+			None => format!("{} at line {}, column {}", e.node, line, column)
+		};
+
+		error(literal, &message)
+	})
+}
+
+///Makes the compiler track the file, so that changes trigger a rebuild.
+fn dependency(path: &Path) -> TokenStream2 {
+	let path = path.to_string_lossy().into_owned();
+	quote!(const _: &'static [u8] = include_bytes!(#path);)
+}
+
+///A `compile_error!` that points at the macro argument.
+fn error(literal: &LitStr, message: &str) -> TokenStream2 {
+	quote_spanned!(literal.span()=> compile_error!(#message))
+}
+
+///A `compile_error!` for an error from `syn`.
+///
+///It's used instead of `syn::Error::to_compile_error`, which refers to the
+///`core` crate in a way that doesn't work in crates using the 2015 edition.
+fn syn_error(error: syn::Error) -> TokenStream2 {
+	let message = error.to_string();
+	quote_spanned!(error.span()=> compile_error!(#message);)
+}
+
+///The line and column of a byte offset, both starting at 1.
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+	let before = &source[..offset];
+	let line = before.matches('\n').count() + 1;
+	let column = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+	(line, column)
+}
+
+fn spanned_token(token: &Spanned<Token>) -> TokenStream2 {
+	let node = match token.node {
+		Token::String(ref s) => quote!(::fragments::Token::String(#s.to_owned())),
+		Token::Placeholder(ref k) => quote!(::fragments::Token::Placeholder(#k.to_owned())),
+		Token::Filtered(ref k, ref filters) => {
+			let filters = filters.iter().map(|filter| {
+				let name = &filter.name;
+				let args = &filter.args;
+				quote!(::fragments::Filter::new(#name.to_owned(), vec![#(#args.to_owned()),*]))
+			});
+			quote!(::fragments::Token::Filtered(#k.to_owned(), vec![#(#filters),*]))
+		},
+		Token::Conditional(ref k, expected, ref tokens) => {
+			let tokens = tokens.iter().map(spanned_token);
+			quote!(::fragments::Token::Conditional(#k.to_owned(), #expected, vec![#(#tokens),*]))
+		},
+		Token::ContentConditional(ref k, expected, ref tokens) => {
+			let tokens = tokens.iter().map(spanned_token);
+			quote!(::fragments::Token::ContentConditional(#k.to_owned(), #expected, vec![#(#tokens),*]))
+		},
+		Token::Generated(ref k, ref args) => {
+			let args = args.iter().map(|arg| {
+				let span = span(arg.span);
+				let arg = &arg.node;
+				quote!(::fragments::Spanned::new(#arg.to_owned(), #span))
+			});
+			quote!(::fragments::Token::Generated(#k.to_owned(), vec![#(#args),*]))
+		},
+		Token::Include(ref k) => quote!(::fragments::Token::Include(#k.to_owned())),
+		Token::Translated(ref id, ref args) => {
+			let args = args.iter().map(|arg| {
+				let name = &arg.name;
+				let value = match arg.value {
+					ArgumentValue::Label(ref label) => quote!(::fragments::ArgumentValue::Label(#label.to_owned())),
+					ArgumentValue::Text(ref text) => quote!(::fragments::ArgumentValue::Text(#text.to_owned()))
+				};
+				quote!(::fragments::Argument::new(#name.to_owned(), #value))
+			});
+			quote!(::fragments::Token::Translated(#id.to_owned(), vec![#(#args),*]))
+		}
+	};
+
+	let span = span(token.span);
+	quote!(::fragments::Spanned::new(#node, #span))
+}
+
+fn span(span: Span) -> TokenStream2 {
+	let start = span.start;
+	let end = span.end;
+	quote!(::fragments::Span::new(#start, #end))
+}
@@ -0,0 +1,866 @@
+use super::{ContentType, Escaping, Argument, ArgumentValue, Lookup, SignificantDigits, ExponentFormat};
+use plural::{PluralCategory, PluralForms};
+use number;
+use escape;
+use resolver;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::collections::{HashMap, HashSet};
+
+///The maximum depth of message references, like `{ -brand }`.
+const MAX_DEPTH: usize = 16;
+
+///Translated messages for one language, like the content of `de.po` or `de.ftl`.
+///
+///Messages are used by translation tokens, like `[[~greeting name=:name]]`,
+///where each argument is either the content of a placeholder, like
+///`:name`, or a literal text, like `title="Dr."`.
+///
+///A gettext catalog is loaded with `from_po`. The ids are the `msgid`s,
+///and the messages use `{name}` for arguments. Messages with plural forms
+///choose a form with the `count` argument and the `Plural-Forms` header.
+///Fuzzy and untranslated entries, and entries with a `msgctxt`, are skipped.
+///
+///A Fluent catalog is loaded with `from_fluent`. The ids are the message
+///names, and the messages use `{ $name }` for arguments, `{ "text" }` for
+///literal text and `{ name }` or `{ -term }` for other messages and terms.
+///Select expressions, like `{ $count -> [one] One item *[other] Many items }`,
+///with one variant per line, choose a variant by an exact number or text,
+///or by the CLDR plural category of a number. Attributes are not supported.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Catalog {
+	language: String,
+	plural_forms: PluralForms,
+	messages: HashMap<String, Message>
+}
+
+impl Catalog {
+	///Create an empty catalog for the language of a tag, like `en` or `de-AT`.
+	pub fn new(language: &str) -> Catalog {
+		Catalog {
+			language: normalize(language),
+			plural_forms: PluralForms::default(),
+			messages: HashMap::new()
+		}
+	}
+
+	///Load a gettext `.po` catalog for the language of a tag.
+	pub fn from_po(language: &str, source: &str) -> Result<Catalog, String> {
+		let mut catalog = Catalog::new(language);
+		let mut entry = PoEntry::default();
+
+		for (index, line) in source.lines().enumerate() {
+			let number = index + 1;
+			let line = line.trim();
+			let at_line = |message: String| format!("line {}: {}", number, message);
+
+			if line.is_empty() {
+				catalog.add_po_entry(std::mem::take(&mut entry))?;
+			} else if let Some(flags) = line.strip_prefix("#,") {
+				if entry.has_message() {
+					catalog.add_po_entry(std::mem::take(&mut entry))?;
+				}
+
+				entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+			} else if line.starts_with('#') {
+				if entry.has_message() {
+					catalog.add_po_entry(std::mem::take(&mut entry))?;
+				}
+			} else if let Some(rest) = line.strip_prefix("msgctxt") {
+				if entry.has_message() {
+					catalog.add_po_entry(std::mem::take(&mut entry))?;
+				}
+
+				entry.context = true;
+				entry.line = number;
+				entry.field = PoField::Context;
+				parse_po_string(rest).map_err(at_line)?;
+			} else if let Some(rest) = line.strip_prefix("msgid_plural") {
+				entry.plural = Some(parse_po_string(rest).map_err(at_line)?);
+				entry.field = PoField::Plural;
+			} else if let Some(rest) = line.strip_prefix("msgid") {
+				if entry.has_message() {
+					catalog.add_po_entry(std::mem::take(&mut entry))?;
+				}
+
+				entry.id = Some(parse_po_string(rest).map_err(at_line)?);
+				entry.line = number;
+				entry.field = PoField::Id;
+			} else if let Some(rest) = line.strip_prefix("msgstr[") {
+				let end = rest.find(']').ok_or_else(|| at_line("expected ']' after the plural index".to_owned()))?;
+				let index = rest[..end].trim().parse::<usize>().map_err(|_| at_line(format!("invalid plural index '{}'", &rest[..end])))?;
+				entry.strings.push((index, parse_po_string(&rest[end + 1..]).map_err(at_line)?));
+				entry.field = PoField::String;
+			} else if let Some(rest) = line.strip_prefix("msgstr") {
+				entry.strings.push((0, parse_po_string(rest).map_err(at_line)?));
+				entry.field = PoField::String;
+			} else if line.starts_with('"') {
+				let continuation = parse_po_string(line).map_err(at_line)?;
+
+				match entry.field {
+					PoField::Id => entry.id.get_or_insert_with(String::new).push_str(&continuation),
+					PoField::Plural => entry.plural.get_or_insert_with(String::new).push_str(&continuation),
+					PoField::String => if let Some(&mut (_, ref mut string)) = entry.strings.last_mut() {
+						string.push_str(&continuation);
+					},
+					PoField::Context => {},
+					PoField::None => return Err(at_line("unexpected string outside of an entry".to_owned()))
+				}
+			} else {
+				return Err(at_line(format!("unexpected '{}'", line)));
+			}
+		}
+
+		catalog.add_po_entry(entry)?;
+		Ok(catalog)
+	}
+
+	///Load a Fluent `.ftl` catalog for the language of a tag.
+	pub fn from_fluent(language: &str, source: &str) -> Result<Catalog, String> {
+		let mut catalog = Catalog::new(language);
+		let lines: Vec<&str> = source.lines().collect();
+		let mut index = 0;
+
+		while index < lines.len() {
+			let line = lines[index];
+			let number = index + 1;
+			let at_line = |message: String| format!("line {}: {}", number, message);
+			index += 1;
+
+			if line.trim().is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			if line.starts_with(char::is_whitespace) {
+				return Err(at_line("expected a message, but found an indented line".to_owned()));
+			}
+
+			let equals = line.find('=').ok_or_else(|| at_line("expected '=' after the message id".to_owned()))?;
+			let id = line[..equals].trim();
+			let name = id.strip_prefix('-').unwrap_or(id);
+
+			if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+				return Err(at_line(format!("invalid message id '{}'", id)));
+			}
+
+			let mut value = line[equals + 1..].trim().to_owned();
+
+			while let Some(&line) = lines.get(index) {
+				if line.trim().is_empty() {
+					match lines[index..].iter().find(|line| !line.trim().is_empty()) {
+						Some(next) if next.starts_with(char::is_whitespace) => {
+							index += 1;
+							continue;
+						},
+						_ => break
+					}
+				}
+
+				//the `}` of a select expression may also end the message
+				if !line.starts_with(char::is_whitespace) && !line.starts_with('}') {
+					break;
+				}
+
+				if line.trim().starts_with('.') {
+					return Err(format!("line {}: attributes are not supported", index + 1));
+				}
+
+				if !value.is_empty() {
+					value.push('\n');
+				}
+
+				value.push_str(line.trim());
+				index += 1;
+			}
+
+			if value.is_empty() {
+				return Err(at_line(format!("the message '{}' has no value", id)));
+			}
+
+			let pattern = parse_fluent_pattern(&mut value.chars().peekable(), false).map_err(at_line)?;
+			catalog.messages.insert(id.to_owned(), Message::Pattern(pattern));
+		}
+
+		Ok(catalog)
+	}
+
+	///Load a catalog from a `.po` or `.ftl` file, where the name of the
+	///file is the language tag, like `de-AT.po`.
+	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Catalog, String> {
+		let path = path.as_ref();
+		let language = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+		let source = fs::read_to_string(path).map_err(|e| format!("io error: {}", e))?;
+		let catalog = match path.extension().and_then(|extension| extension.to_str()) {
+			Some("po") => Catalog::from_po(language, &source),
+			Some("ftl") => Catalog::from_fluent(language, &source),
+			_ => return Err(format!("unknown catalog format: {}", path.display()))
+		};
+
+		catalog.map_err(|e| format!("{}: {}", path.display(), e))
+	}
+
+	///The normalized language tag, like `de-at`.
+	pub fn language(&self) -> &str {
+		&self.language
+	}
+
+	///Check if the catalog has a message for `id`.
+	pub fn contains(&self, id: &str) -> bool {
+		self.messages.contains_key(id)
+	}
+
+	///The ids of the messages, in sorted order.
+	pub fn ids(&self) -> Vec<&str> {
+		let mut ids: Vec<&str> = self.messages.keys().map(|id| &**id).collect();
+		ids.sort();
+		ids
+	}
+
+	///The ids of the messages that aren't in `used`, in sorted order.
+	///
+	///Terms, like `-brand`, and messages that other messages refer to are
+	///left out, since they are used through those messages.
+	pub fn unused_ids(&self, used: &[&str]) -> Vec<&str> {
+		let mut referenced = HashSet::new();
+		for message in self.messages.values() {
+			match *message {
+				Message::Pattern(ref pattern) => collect_references(pattern, &mut referenced),
+				Message::Forms(ref forms) => for pattern in forms.iter() {
+					collect_references(pattern, &mut referenced);
+				}
+			}
+		}
+
+		self.ids()
+			.into_iter()
+			.filter(|id| !id.starts_with('-') && !used.contains(id) && !referenced.contains(id))
+			.collect()
+	}
+
+	///The number of messages.
+	pub fn len(&self) -> usize {
+		self.messages.len()
+	}
+
+	///Check if the catalog has no messages.
+	pub fn is_empty(&self) -> bool {
+		self.messages.is_empty()
+	}
+
+	fn add_po_entry(&mut self, entry: PoEntry) -> Result<(), String> {
+		let PoEntry { id, plural, mut strings, fuzzy, context, line, .. } = entry;
+		let at_line = |message: String| format!("line {}: {}", line, message);
+
+		let id = match id {
+			Some(id) => id,
+			None => return Ok(())
+		};
+
+		if id.is_empty() {
+			let header = strings.first().map(|(_, header)| &**header).unwrap_or("");
+			for field in header.lines() {
+				if let Some(forms) = field.strip_prefix("Plural-Forms:") {
+					self.plural_forms = PluralForms::parse(forms).map_err(at_line)?;
+				}
+			}
+
+			return Ok(());
+		}
+
+		if fuzzy || context || strings.is_empty() || strings.iter().any(|(_, string)| string.is_empty()) {
+			return Ok(());
+		}
+
+		let message = if plural.is_some() {
+			strings.sort_by_key(|&(index, _)| index);
+			let forms = strings.iter().map(|(_, string)| parse_po_pattern(string)).collect::<Result<_, _>>();
+			Message::Forms(forms.map_err(at_line)?)
+		} else {
+			Message::Pattern(parse_po_pattern(&strings[0].1).map_err(at_line)?)
+		};
+
+		self.messages.insert(id, message);
+		Ok(())
+	}
+}
+
+
+
+///Message catalogs for any number of languages.
+///
+///A `Translations` is shared between templates with `set_translations`,
+///and the languages that are used, and their order, are chosen with
+///`set_languages` on a `Template`, `Context` or `Shell`.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Translations {
+	catalogs: Vec<Catalog>
+}
+
+impl Translations {
+	///Create an empty set of catalogs.
+	pub fn new() -> Translations {
+		Translations {
+			catalogs: Vec::new()
+		}
+	}
+
+	///Load every `.po` and `.ftl` file in a directory, as described for
+	///`Catalog::from_file`.
+	pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Translations, String> {
+		let mut paths = Vec::new();
+
+		for entry in fs::read_dir(path).map_err(|e| format!("io error: {}", e))? {
+			let path = entry.map_err(|e| format!("io error: {}", e))?.path();
+			let extension = path.extension().and_then(|extension| extension.to_str());
+
+			if extension == Some("po") || extension == Some("ftl") {
+				paths.push(path);
+			}
+		}
+
+		paths.sort();
+
+		let mut translations = Translations::new();
+		for path in paths.iter() {
+			translations.add(Catalog::from_file(path)?);
+		}
+
+		Ok(translations)
+	}
+
+	///Add a catalog. Catalogs for the same language are searched in the
+	///order they were added.
+	pub fn add(&mut self, catalog: Catalog) {
+		self.catalogs.push(catalog);
+	}
+
+	///The catalogs, in the order they were added.
+	pub fn catalogs(&self) -> &[Catalog] {
+		&self.catalogs
+	}
+
+	///Find the first catalog for the language of a tag.
+	pub fn get(&self, language: &str) -> Option<&Catalog> {
+		let language = normalize(language);
+		self.catalogs.iter().find(|catalog| catalog.language == language)
+	}
+
+	///Find the message for `id` in the first language of `languages` that
+	///has it. Each language falls back to its more general tags, so `de-AT`
+	///is followed by `de`, before the next language is tried.
+	fn find(&self, languages: &[String], id: &str) -> Option<(&Catalog, &Message)> {
+		for language in fallback_chain(languages).iter() {
+			let found = self.catalogs.iter()
+				.filter(|catalog| catalog.language == *language)
+				.filter_map(|catalog| catalog.messages.get(id).map(|message| (catalog, message)))
+				.next();
+
+			if found.is_some() {
+				return found;
+			}
+		}
+
+		None
+	}
+}
+
+fn collect_references<'a>(pattern: &'a [Part], referenced: &mut HashSet<&'a str>) {
+	for part in pattern.iter() {
+		match *part {
+			Part::Reference(ref id) => {
+				referenced.insert(id);
+			},
+			Part::Select(_, ref variants) => for variant in variants.iter() {
+				collect_references(&variant.pattern, referenced);
+			},
+			Part::Text(_) | Part::Variable(_) => {}
+		}
+	}
+}
+
+///The languages of `languages`, each followed by its more general tags.
+fn fallback_chain(languages: &[String]) -> Vec<String> {
+	let mut chain: Vec<String> = Vec::new();
+
+	for language in languages.iter() {
+		let mut tag = normalize(language);
+
+		loop {
+			if !chain.contains(&tag) {
+				chain.push(tag.clone());
+			}
+
+			match tag.rfind('-') {
+				Some(index) => tag.truncate(index),
+				None => break
+			}
+		}
+	}
+
+	chain
+}
+
+fn normalize(language: &str) -> String {
+	language.replace('_', "-").to_lowercase()
+}
+
+
+
+///Write the message `id` with `args`, using the translations and languages
+///of `lookup`. The content of placeholders is escaped with `escaping`,
+///while the messages and literal arguments are written as they are. The
+///id itself is written, with `{name}` arguments, if there's no translation.
+pub fn write_translation(lookup: &dyn Lookup, id: &str, args: &[Argument], escaping: &Escaping, f: &mut fmt::Formatter) -> fmt::Result {
+	let resolved: Vec<Option<ContentType>> = args.iter().map(|arg| match arg.value {
+		ArgumentValue::Label(ref label) if lookup.get_content(label).is_none() => resolver::content(lookup, label),
+		_ => None
+	}).collect();
+
+	let values: Vec<(&str, Value)> = args.iter().zip(resolved.iter()).map(|(arg, resolved)| {
+		let value = match arg.value {
+			ArgumentValue::Label(ref label) => match lookup.get_content(label).or(resolved.as_ref()) {
+				Some(content) => Value::Content(content),
+				None => Value::Missing
+			},
+			ArgumentValue::Text(ref text) => Value::Text(text)
+		};
+
+		(&*arg.name, value)
+	}).collect();
+
+	let writer = Writer {
+		values: &values,
+		escaping
+	};
+
+	if let Some((catalog, message)) = lookup.get_translations().and_then(|translations| translations.find(lookup.get_languages(), id)) {
+		return writer.write_message(catalog, message, 0, f);
+	}
+
+	match parse_po_pattern(id) {
+		Ok(pattern) => writer.write_pattern(None, &pattern, 0, f),
+		Err(_) => f.write_str(id)
+	}
+}
+
+///A translated message.
+#[derive(Clone, PartialEq, Debug)]
+enum Message {
+	Pattern(Vec<Part>),
+	///Plural forms from a gettext catalog.
+	Forms(Vec<Vec<Part>>)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+	Text(String),
+	Variable(String),
+	Reference(String),
+	Select(String, Vec<Variant>)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Variant {
+	key: String,
+	default: bool,
+	pattern: Vec<Part>
+}
+
+///The value of an argument.
+enum Value<'a, 'c: 'a> {
+	Content(&'a ContentType<'c>),
+	Text(&'a str),
+	Missing
+}
+
+impl<'a, 'c> Value<'a, 'c> {
+	///The value as a number, like `12` or `2.50`, or as text.
+	fn to_text(&self) -> Option<String> {
+		match *self {
+			Value::Content(content) => content_text(content),
+			Value::Text(text) => Some(text.to_owned()),
+			Value::Missing => None
+		}
+	}
+}
+
+fn content_text(content: &ContentType) -> Option<String> {
+	match *content {
+		ContentType::Float(v) => Some(number::format_float(v, SignificantDigits::DigAll, ExponentFormat::ExpNone)),
+		ContentType::FormattedFloat(v, digits, _) => Some(number::format_float(v, digits, ExponentFormat::ExpNone)),
+		ContentType::Int(_) | ContentType::UnsignedInt(_) | ContentType::String(_) | ContentType::StringSlice(_) => Some(content.to_string()),
+		ContentType::Safe(ref content) => content_text(content),
+		ContentType::Lazy(ref lazy) => lazy.with(content_text),
+		_ => None
+	}
+}
+
+struct Writer<'v, 'a: 'v, 'c: 'a> {
+	values: &'v [(&'a str, Value<'a, 'c>)],
+	escaping: &'v Escaping
+}
+
+impl<'v, 'a, 'c> Writer<'v, 'a, 'c> {
+	fn get(&self, name: &str) -> Option<&Value<'a, 'c>> {
+		self.values.iter().find(|&&(n, _)| n == name).map(|(_, value)| value)
+	}
+
+	fn write_message(&self, catalog: &Catalog, message: &Message, depth: usize, f: &mut fmt::Formatter) -> fmt::Result {
+		match *message {
+			Message::Pattern(ref pattern) => self.write_pattern(Some(catalog), pattern, depth, f),
+			Message::Forms(ref forms) => {
+				let count = self.get("count")
+					.and_then(Value::to_text)
+					.and_then(|text| text.trim_start_matches('-').split('.').next().and_then(|i| i.parse::<u64>().ok()));
+				let index = count.map_or(0, |n| catalog.plural_forms.index(n));
+
+				match forms.get(index).or_else(|| forms.first()) {
+					Some(pattern) => self.write_pattern(Some(catalog), pattern, depth, f),
+					None => Ok(())
+				}
+			}
+		}
+	}
+
+	fn write_pattern(&self, catalog: Option<&Catalog>, pattern: &[Part], depth: usize, f: &mut fmt::Formatter) -> fmt::Result {
+		for part in pattern.iter() {
+			match *part {
+				Part::Text(ref text) => f.write_str(text)?,
+				Part::Variable(ref name) => match self.get(name) {
+					Some(&Value::Content(content)) => escape::write_content(content, self.escaping, f)?,
+					Some(&Value::Text(text)) => f.write_str(text)?,
+					Some(&Value::Missing) | None => {}
+				},
+				Part::Reference(ref id) => {
+					let message = catalog.and_then(|catalog| catalog.messages.get(id).map(|message| (catalog, message)));
+
+					match message {
+						Some((catalog, message)) if depth < MAX_DEPTH => self.write_message(catalog, message, depth + 1, f)?,
+						_ => f.write_str(id)?
+					}
+				},
+				Part::Select(ref name, ref variants) => {
+					let text = self.get(name).and_then(Value::to_text);
+					let language = catalog.map_or("en", |catalog| &catalog.language);
+
+					if let Some(variant) = select(variants, text.as_deref(), language) {
+						self.write_pattern(catalog, &variant.pattern, depth, f)?;
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+///Choose a variant with the same number or text as `text`, then one with
+///its plural category, and the default variant otherwise.
+fn select<'a>(variants: &'a [Variant], text: Option<&str>, language: &str) -> Option<&'a Variant> {
+	if let Some(text) = text {
+		let number = text.parse::<f64>().ok();
+		let exact = variants.iter().find(|variant| match (number, variant.key.parse::<f64>()) {
+			(Some(number), Ok(key)) => number == key,
+			_ => variant.key == text
+		});
+
+		if exact.is_some() {
+			return exact;
+		}
+
+		if number.is_some() {
+			let category = PluralCategory::of(language, text);
+			let plural = variants.iter().find(|variant| PluralCategory::from_name(&variant.key) == Some(category));
+
+			if plural.is_some() {
+				return plural;
+			}
+		}
+	}
+
+	variants.iter().find(|variant| variant.default)
+}
+
+
+
+#[derive(Default)]
+struct PoEntry {
+	id: Option<String>,
+	plural: Option<String>,
+	strings: Vec<(usize, String)>,
+	fuzzy: bool,
+	context: bool,
+	line: usize,
+	field: PoField
+}
+
+impl PoEntry {
+	fn has_message(&self) -> bool {
+		!self.strings.is_empty()
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum PoField {
+	#[default]
+	None,
+	Context,
+	Id,
+	Plural,
+	String
+}
+
+///Parse a quoted `.po` string, like `"Hello,\n"`.
+fn parse_po_string(source: &str) -> Result<String, String> {
+	let source = source.trim();
+
+	if source.len() < 2 || !source.starts_with('"') || !source.ends_with('"') {
+		return Err(format!("expected a quoted string, but found '{}'", source));
+	}
+
+	let mut string = String::new();
+	let mut chars = source[1..source.len() - 1].chars();
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			string.push(c);
+			continue;
+		}
+
+		match chars.next() {
+			Some('n') => string.push('\n'),
+			Some('t') => string.push('\t'),
+			Some('r') => string.push('\r'),
+			Some(c @ '"') | Some(c @ '\\') => string.push(c),
+			Some(c) => return Err(format!("unknown escape sequence '\\{}'", c)),
+			None => return Err("unexpected '\\' at the end of the string".to_owned())
+		}
+	}
+
+	Ok(string)
+}
+
+///Parse a gettext message, with arguments like `{name}` and `{{` and `}}` for braces.
+fn parse_po_pattern(source: &str) -> Result<Vec<Part>, String> {
+	let mut parts = Vec::new();
+	let mut text = String::new();
+	let mut chars = source.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'{' if chars.peek() == Some(&'{') => {
+				chars.next();
+				text.push('{');
+			},
+			'}' if chars.peek() == Some(&'}') => {
+				chars.next();
+				text.push('}');
+			},
+			'{' => {
+				let mut name = String::new();
+
+				loop {
+					match chars.next() {
+						Some('}') => break,
+						Some(c) => name.push(c),
+						None => return Err(format!("unclosed '{{' in '{}'", source))
+					}
+				}
+
+				push_text(&mut parts, &mut text);
+				parts.push(Part::Variable(name.trim().to_owned()));
+			},
+			'}' => return Err(format!("unexpected '}}' in '{}'", source)),
+			c => text.push(c)
+		}
+	}
+
+	push_text(&mut parts, &mut text);
+	Ok(parts)
+}
+
+///Parse a Fluent pattern. The pattern of a variant ends with the line.
+fn parse_fluent_pattern(chars: &mut Peekable<Chars>, variant: bool) -> Result<Vec<Part>, String> {
+	let mut parts = Vec::new();
+	let mut text = String::new();
+
+	loop {
+		match chars.peek() {
+			Some(&'\n') | Some(&'}') if variant => break,
+			Some(&'}') => return Err("unexpected '}'".to_owned()),
+			Some(&'{') => {
+				chars.next();
+				push_text(&mut parts, &mut text);
+				parts.push(parse_placeable(chars)?);
+			},
+			Some(&c) => {
+				chars.next();
+				text.push(c);
+			},
+			None => break
+		}
+	}
+
+	if variant {
+		let trimmed = text.trim_end().len();
+		text.truncate(trimmed);
+	}
+
+	push_text(&mut parts, &mut text);
+	Ok(parts)
+}
+
+///Parse the rest of a placeable, after `{`.
+fn parse_placeable(chars: &mut Peekable<Chars>) -> Result<Part, String> {
+	skip_whitespace(chars, true);
+
+	let part = match chars.peek() {
+		Some(&'"') => {
+			chars.next();
+			let mut text = String::new();
+
+			loop {
+				match chars.next() {
+					Some('"') => break,
+					Some('\\') => match chars.next() {
+						Some(c) => text.push(c),
+						None => return Err("unclosed string literal".to_owned())
+					},
+					Some(c) => text.push(c),
+					None => return Err("unclosed string literal".to_owned())
+				}
+			}
+
+			Part::Text(text)
+		},
+		Some(&'$') => {
+			chars.next();
+			let mut name = parse_identifier(chars)?;
+
+			//identifiers may end with `-`, so it can belong to `->`
+			let select = if name.ends_with('-') && chars.peek() == Some(&'>') {
+				name.pop();
+				chars.next();
+				true
+			} else {
+				skip_whitespace(chars, true);
+
+				if chars.peek() == Some(&'-') {
+					chars.next();
+					if chars.next() != Some('>') {
+						return Err("expected '->' after the selector".to_owned());
+					}
+
+					true
+				} else {
+					false
+				}
+			};
+
+			if select {
+				Part::Select(name, parse_variants(chars)?)
+			} else {
+				Part::Variable(name)
+			}
+		},
+		Some(_) => Part::Reference(parse_identifier(chars)?),
+		None => return Err("unclosed '{'".to_owned())
+	};
+
+	skip_whitespace(chars, true);
+
+	if chars.next() == Some('}') {
+		Ok(part)
+	} else {
+		Err("expected '}'".to_owned())
+	}
+}
+
+fn parse_variants(chars: &mut Peekable<Chars>) -> Result<Vec<Variant>, String> {
+	let mut variants = Vec::new();
+
+	loop {
+		skip_whitespace(chars, true);
+
+		let default = match chars.peek() {
+			Some(&'}') => break,
+			Some(&'*') => {
+				chars.next();
+				true
+			},
+			_ => false
+		};
+
+		if chars.next() != Some('[') {
+			return Err("expected a variant, like '[one]'".to_owned());
+		}
+
+		let mut key = String::new();
+
+		loop {
+			match chars.next() {
+				Some(']') => break,
+				Some(c) => key.push(c),
+				None => return Err("unclosed variant key".to_owned())
+			}
+		}
+
+		skip_whitespace(chars, false);
+
+		variants.push(Variant {
+			key: key.trim().to_owned(),
+			default,
+			pattern: parse_fluent_pattern(chars, true)?
+		});
+	}
+
+	if variants.iter().filter(|variant| variant.default).count() != 1 {
+		return Err("a select expression needs exactly one default variant, marked with '*'".to_owned());
+	}
+
+	Ok(variants)
+}
+
+fn parse_identifier(chars: &mut Peekable<Chars>) -> Result<String, String> {
+	let mut name = String::new();
+
+	if chars.peek() == Some(&'-') {
+		chars.next();
+		name.push('-');
+	}
+
+	while let Some(&c) = chars.peek() {
+		if c.is_alphanumeric() || c == '-' || c == '_' {
+			chars.next();
+			name.push(c);
+		} else {
+			break;
+		}
+	}
+
+	if name.is_empty() || name == "-" {
+		Err("expected an identifier".to_owned())
+	} else {
+		Ok(name)
+	}
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>, newlines: bool) {
+	while let Some(&c) = chars.peek() {
+		if c == ' ' || c == '\t' || (newlines && c == '\n') {
+			chars.next();
+		} else {
+			break;
+		}
+	}
+}
+
+fn push_text(parts: &mut Vec<Part>, text: &mut String) {
+	if !text.is_empty() {
+		parts.push(Part::Text(std::mem::take(text)));
+	}
+}
@@ -0,0 +1,214 @@
+use super::{Token, Spanned, Lookup};
+use introspect::{self, Kind, Usage};
+use resolver;
+
+use std::collections::{HashMap, HashSet};
+use std::cmp::{min, max};
+
+///The labels that have content, conditions or generators in a `Lookup`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct DefinedLabels {
+	///Labels with content.
+	pub content: HashSet<String>,
+	///Labels of conditions that are explicitly set, and their values.
+	pub conditions: HashMap<String, bool>,
+	///Labels with generators.
+	pub generators: HashSet<String>
+}
+
+impl Default for DefinedLabels {
+	fn default() -> DefinedLabels {
+		DefinedLabels::new()
+	}
+}
+
+impl DefinedLabels {
+	///Create an empty set of labels.
+	pub fn new() -> DefinedLabels {
+		DefinedLabels {
+			content: HashSet::new(),
+			conditions: HashMap::new(),
+			generators: HashSet::new()
+		}
+	}
+
+	///Check if `label` has content, either directly or through a path
+	///prefix, like `user` for `user.name`.
+	pub fn has_content(&self, label: &str) -> bool {
+		self.content.contains(label) || self.has_content_prefix(label)
+	}
+
+	///Check if a path prefix of `label`, like `user` for `user.name`, has content.
+	pub fn has_content_prefix(&self, label: &str) -> bool {
+		label.match_indices('.').any(|(dot, _)| self.content.contains(&label[..dot]))
+	}
+}
+
+///A missing label that looks like a misspelled unused label.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Suggestion {
+	///The label that is used in the template, but is missing.
+	pub missing: String,
+	///The label that is defined, but isn't used in the template.
+	pub unused: String,
+	///The edit distance between the labels.
+	pub distance: usize
+}
+
+///The result of checking a template against its content.
+///
+///Missing labels are listed in the order they first appear in the template,
+///while unused labels are sorted.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Report {
+	///Placeholders without content. Placeholders that are only used within
+	///`[[?:label]]...[[/]]` blocks for the same label, or that the resolver
+	///has content for, are not included.
+	pub missing_content: Vec<String>,
+	///Conditions that are used in the template, but never set or known by
+	///the resolver. They are `false` by default.
+	pub unset_conditions: Vec<String>,
+	///Generator tokens without generators.
+	pub missing_generators: Vec<String>,
+	///Content that isn't used by any placeholder or content condition.
+	pub unused_content: Vec<String>,
+	///Conditions that are set, but not used by the template.
+	pub unused_conditions: Vec<String>,
+	///Generators that aren't used by the template.
+	pub unused_generators: Vec<String>,
+	///Missing labels that may be misspelled versions of unused labels.
+	pub suggestions: Vec<Suggestion>
+}
+
+impl Report {
+	///Check if nothing is missing or unused.
+	///
+	///Unset conditions are not counted, since they are `false` by default.
+	pub fn is_ok(&self) -> bool {
+		self.missing_content.is_empty() &&
+		self.missing_generators.is_empty() &&
+		self.unused_content.is_empty() &&
+		self.unused_conditions.is_empty() &&
+		self.unused_generators.is_empty()
+	}
+}
+
+///Compare the labels in `tokens` with the labels defined in `lookup`.
+pub fn check(tokens: &[Spanned<Token>], lookup: &dyn Lookup) -> Report {
+	let mut defined = DefinedLabels::new();
+	lookup.collect_labels(&mut defined);
+
+	let placeholders = introspect::find_usages(tokens, Kind::Placeholder);
+	let content_conditions = introspect::find_usages(tokens, Kind::ContentCondition);
+	let conditions = introspect::find_usages(tokens, Kind::Condition);
+	let generators = introspect::find_usages(tokens, Kind::Generator);
+
+	let missing_content: Vec<String> = placeholders.iter()
+		.filter(|usage| (!defined.has_content(&usage.label) || !lookup.is_content_defined(&usage.label)) && !is_guarded(usage))
+		.filter(|usage| !resolver::is_defined(lookup, &usage.label))
+		.map(|usage| usage.label.clone())
+		.collect();
+
+	let unset_conditions: Vec<String> = conditions.iter()
+		.filter(|usage| !defined.conditions.contains_key(&usage.label) && !resolver::is_defined(lookup, &usage.label))
+		.map(|usage| usage.label.clone())
+		.collect();
+
+	let missing_generators: Vec<String> = generators.iter()
+		.filter(|usage| !defined.generators.contains(&usage.label))
+		.map(|usage| usage.label.clone())
+		.collect();
+
+	let unused_content = unused(defined.content.iter(), &[&placeholders, &content_conditions]);
+	let unused_conditions = unused(defined.conditions.keys(), &[&conditions]);
+	let unused_generators = unused(defined.generators.iter(), &[&generators]);
+
+	let mut suggestions = Vec::new();
+	suggest(&missing_content, &unused_content, &mut suggestions);
+	suggest(&unset_conditions, &unused_conditions, &mut suggestions);
+	suggest(&missing_generators, &unused_generators, &mut suggestions);
+
+	Report {
+		missing_content,
+		unset_conditions,
+		missing_generators,
+		unused_content,
+		unused_conditions,
+		unused_generators,
+		suggestions
+	}
+}
+
+fn is_guarded(usage: &Usage) -> bool {
+	usage.occurrences.iter().all(|occurrence| occurrence.blocks.iter().any(|block| {
+		block.content_condition && block.expected && block.label == usage.label
+	}))
+}
+
+fn unused<'a, I: Iterator<Item=&'a String>>(defined: I, used: &[&Vec<Usage>]) -> Vec<String> {
+	let mut unused: Vec<String> = defined
+		.filter(|label| !used.iter().any(|usages| usages.iter().any(|usage| is_used_by(label, &usage.label))))
+		.cloned()
+		.collect();
+	unused.sort();
+	unused
+}
+
+///A label is used by itself and by paths that start with it, like `user.name` for `user`.
+fn is_used_by(label: &str, used: &str) -> bool {
+	used == label || (used.starts_with(label) && used[label.len()..].starts_with('.'))
+}
+
+fn suggest(missing: &[String], unused: &[String], suggestions: &mut Vec<Suggestion>) {
+	for label in missing.iter() {
+		let max_distance = max(1, label.chars().count() / 3);
+
+		let best = unused.iter()
+			.map(|candidate| (edit_distance(label, candidate), candidate))
+			.filter(|&(distance, _)| distance <= max_distance)
+			.min_by_key(|&(distance, _)| distance);
+
+		if let Some((distance, candidate)) = best {
+			suggestions.push(Suggestion {
+				missing: label.clone(),
+				unused: candidate.clone(),
+				distance
+			});
+		}
+	}
+}
+
+///The edit distance between `a` and `b`, counted in characters, where
+///insertions, deletions, substitutions and swapped neighbors cost one each.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.chars().collect();
+	let b: Vec<char> = b.chars().collect();
+	let width = b.len() + 1;
+	let mut distances = vec![0; (a.len() + 1) * width];
+
+	for i in 0..a.len() + 1 {
+		distances[i * width] = i;
+	}
+
+	for (j, distance) in distances.iter_mut().enumerate().take(width) {
+		*distance = j;
+	}
+
+	for i in 1..a.len() + 1 {
+		for j in 1..b.len() + 1 {
+			let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+			let mut distance = min(
+				distances[(i - 1) * width + j - 1] + cost,
+				min(distances[(i - 1) * width + j], distances[i * width + j - 1]) + 1
+			);
+
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				distance = min(distance, distances[(i - 2) * width + j - 2] + 1);
+			}
+
+			distances[i * width + j] = distance;
+		}
+	}
+
+	distances[a.len() * width + b.len()]
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+///Timezone offsets are less than a day, in either direction.
+const OFFSETS: RangeInclusive<i32> = -86399..=86399;
+
+///A point in time, with the offset of a timezone.
+///
+///It's displayed as RFC 3339, like `2015-03-14T09:26:53+01:00`, and it can
+///be formatted in templates with the `date` filter, using strftime
+///patterns, like `[[:created|date "%Y-%m-%d"]]`, or relative to the time
+///of rendering with the `relative` filter, like `3 days ago`. The time of
+///rendering can be replaced with `set_now` on a `Template`, `Context` or
+///`Shell`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DateTime {
+	timestamp: i64,
+	offset: i32
+}
+
+impl DateTime {
+	///Create a `DateTime` from a date and a time in the timezone with the
+	///offset `offset`, in seconds east of UTC. The result is `None` if any
+	///of the parts are out of range, or if the time is too far from 1970 to
+	///be counted in seconds.
+	pub fn new(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: u32, offset: i32) -> Option<DateTime> {
+		let valid = (1..=12).contains(&month) &&
+			day >= 1 && day <= days_in_month(year, month) &&
+			hour < 24 && minute < 60 && second < 60 &&
+			OFFSETS.contains(&offset) &&
+			//keeps the day count from overflowing, long before the timestamp does
+			year.unsigned_abs() < 1 << 40;
+
+		if !valid {
+			return None;
+		}
+
+		let seconds = (hour * 3600 + minute * 60 + second) as i64 - offset as i64;
+		let timestamp = days_from_civil(year, month, day).checked_mul(86400)?.checked_add(seconds)?;
+		Some(DateTime {
+			timestamp,
+			offset
+		})
+	}
+
+	///Create a `DateTime` from the number of seconds since 1970-01-01 UTC.
+	///It will be shown with the offset `offset`, in seconds east of UTC. The
+	///result is `None` if the offset is a day or more.
+	pub fn from_timestamp(timestamp: i64, offset: i32) -> Option<DateTime> {
+		if OFFSETS.contains(&offset) {
+			Some(DateTime {
+				timestamp,
+				offset
+			})
+		} else {
+			None
+		}
+	}
+
+	///Create a UTC `DateTime` from a `SystemTime`. Fractions of seconds are left out.
+	pub fn from_system_time(time: SystemTime) -> DateTime {
+		let timestamp = match time.duration_since(UNIX_EPOCH) {
+			Ok(duration) => duration.as_secs() as i64,
+			Err(e) => {
+				let before = e.duration();
+				-(before.as_secs() as i64) - if before.subsec_nanos() > 0 { 1 } else { 0 }
+			}
+		};
+
+		DateTime {
+			timestamp,
+			offset: 0
+		}
+	}
+
+	///The current time in UTC.
+	pub fn now() -> DateTime {
+		DateTime::from_system_time(SystemTime::now())
+	}
+
+	///The same point in time, shown with the offset `offset`, in seconds east
+	///of UTC. The result is `None` if the offset is a day or more.
+	pub fn with_offset(self, offset: i32) -> Option<DateTime> {
+		DateTime::from_timestamp(self.timestamp, offset)
+	}
+
+	///The number of seconds since 1970-01-01 UTC.
+	pub fn timestamp(&self) -> i64 {
+		self.timestamp
+	}
+
+	///The offset of the timezone, in seconds east of UTC.
+	pub fn offset(&self) -> i32 {
+		self.offset
+	}
+
+	///Format the date and time with a strftime pattern, like `%Y-%m-%d %H:%M`.
+	///
+	///The supported directives are `%Y`, `%C`, `%y`, `%m`, `%d`, `%e`, `%j`,
+	///`%H`, `%I`, `%M`, `%S`, `%p`, `%a`, `%A`, `%b`, `%h`, `%B`, `%u`, `%w`,
+	///`%s`, `%z`, `%:z`, `%Z`, `%F`, `%T`, `%R`, `%D`, `%n`, `%t` and `%%`.
+	///Numbers can be written without padding with a `-`, like `%-d`.
+	pub fn format<'a>(&'a self, pattern: &'a str) -> Formatted<'a> {
+		Formatted {
+			date: self,
+			pattern
+		}
+	}
+
+	///Describe the date and time relative to `now`, like `3 days ago` or `in 2 hours`.
+	pub fn relative_to(&self, now: &DateTime) -> String {
+		let difference = now.timestamp.saturating_sub(self.timestamp);
+		let seconds = difference.unsigned_abs();
+
+		let (amount, unit) = if seconds < 60 {
+			return "just now".to_owned();
+		} else if seconds < 3600 {
+			(seconds / 60, "minute")
+		} else if seconds < 86400 {
+			(seconds / 3600, "hour")
+		} else if seconds < 30 * 86400 {
+			(seconds / 86400, "day")
+		} else if seconds < 365 * 86400 {
+			(seconds / (30 * 86400), "month")
+		} else {
+			(seconds / (365 * 86400), "year")
+		};
+
+		let plural = if amount == 1 { "" } else { "s" };
+
+		if difference > 0 {
+			format!("{} {}{} ago", amount, unit, plural)
+		} else {
+			format!("in {} {}{}", amount, unit, plural)
+		}
+	}
+
+	///The date and time parts in the timezone of the offset.
+	fn parts(&self) -> Parts {
+		let local = self.timestamp.saturating_add(self.offset as i64);
+		let days = local.div_euclid(86400);
+		let seconds = local.rem_euclid(86400) as u32;
+		let (year, month, day) = civil_from_days(days);
+
+		Parts {
+			year,
+			month,
+			day,
+			hour: seconds / 3600,
+			minute: seconds / 60 % 60,
+			second: seconds % 60,
+			weekday: (days + 4).rem_euclid(7) as usize,
+			day_of_year: (days - days_from_civil(year, 1, 1) + 1) as u32
+		}
+	}
+}
+
+impl fmt::Display for DateTime {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.offset == 0 {
+			write_pattern(self, "%Y-%m-%dT%H:%M:%SZ", f)
+		} else {
+			write_pattern(self, "%Y-%m-%dT%H:%M:%S%:z", f)
+		}
+	}
+}
+
+///A `DateTime`, displayed with a strftime pattern.
+pub struct Formatted<'a> {
+	date: &'a DateTime,
+	pattern: &'a str
+}
+
+impl<'a> fmt::Display for Formatted<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write_pattern(self.date, self.pattern, f)
+	}
+}
+
+struct Parts {
+	year: i64,
+	month: u32,
+	day: u32,
+	hour: u32,
+	minute: u32,
+	second: u32,
+	weekday: usize,
+	day_of_year: u32
+}
+
+fn write_pattern(date: &DateTime, pattern: &str, f: &mut fmt::Formatter) -> fmt::Result {
+	let parts = date.parts();
+	let mut chars = pattern.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			fmt::Write::write_char(f, c)?;
+			continue;
+		}
+
+		let mut directive = chars.next();
+		let pad = directive != Some('-');
+		let colon = directive == Some(':');
+		if !pad || colon {
+			directive = chars.next();
+		}
+
+		let number = |f: &mut fmt::Formatter, value: i64, width: usize| if pad {
+			write!(f, "{:01$}", value, width)
+		} else {
+			write!(f, "{}", value)
+		};
+
+		match directive {
+			Some('Y') => number(f, parts.year, 4)?,
+			Some('C') => number(f, parts.year.div_euclid(100), 2)?,
+			Some('y') => number(f, parts.year.rem_euclid(100), 2)?,
+			Some('m') => number(f, parts.month as i64, 2)?,
+			Some('d') => number(f, parts.day as i64, 2)?,
+			Some('e') if pad => write!(f, "{:2}", parts.day)?,
+			Some('e') => number(f, parts.day as i64, 2)?,
+			Some('j') => number(f, parts.day_of_year as i64, 3)?,
+			Some('H') => number(f, parts.hour as i64, 2)?,
+			Some('I') => number(f, ((parts.hour + 11) % 12 + 1) as i64, 2)?,
+			Some('M') => number(f, parts.minute as i64, 2)?,
+			Some('S') => number(f, parts.second as i64, 2)?,
+			Some('p') => f.write_str(if parts.hour < 12 { "AM" } else { "PM" })?,
+			Some('a') => f.write_str(&WEEKDAYS[parts.weekday][..3])?,
+			Some('A') => f.write_str(WEEKDAYS[parts.weekday])?,
+			Some('b') | Some('h') => f.write_str(&MONTHS[parts.month as usize - 1][..3])?,
+			Some('B') => f.write_str(MONTHS[parts.month as usize - 1])?,
+			Some('u') => write!(f, "{}", if parts.weekday == 0 { 7 } else { parts.weekday })?,
+			Some('w') => write!(f, "{}", parts.weekday)?,
+			Some('s') => write!(f, "{}", date.timestamp)?,
+			Some('z') => write_offset(date.offset, colon, f)?,
+			Some('Z') if date.offset == 0 => f.write_str("UTC")?,
+			Some('Z') => write_offset(date.offset, true, f)?,
+			Some('F') => write_pattern(date, "%Y-%m-%d", f)?,
+			Some('T') => write_pattern(date, "%H:%M:%S", f)?,
+			Some('R') => write_pattern(date, "%H:%M", f)?,
+			Some('D') => write_pattern(date, "%m/%d/%y", f)?,
+			Some('n') => f.write_str("\n")?,
+			Some('t') => f.write_str("\t")?,
+			Some('%') => f.write_str("%")?,
+			Some(c) => {
+				fmt::Write::write_char(f, '%')?;
+				fmt::Write::write_char(f, c)?;
+			},
+			None => f.write_str("%")?
+		}
+	}
+
+	Ok(())
+}
+
+fn write_offset(offset: i32, colon: bool, f: &mut fmt::Formatter) -> fmt::Result {
+	let sign = if offset < 0 { '-' } else { '+' };
+	let minutes = offset.abs() / 60;
+
+	if colon {
+		write!(f, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+	} else {
+		write!(f, "{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+	}
+}
+
+fn is_leap_year(year: i64) -> bool {
+	year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+	match month {
+		2 if is_leap_year(year) => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31
+	}
+}
+
+///The number of days since 1970-01-01 for a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year.rem_euclid(400);
+	let month = month as i64;
+	let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+///The date for a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let days = days + 719468;
+	let era = days.div_euclid(146097);
+	let day_of_era = days.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+	let year = year_of_era + era * 400;
+
+	(if month <= 2 { year + 1 } else { year }, month, day)
+}
@@ -0,0 +1,225 @@
+use super::ContentType;
+
+use std::fmt;
+use std::sync::Arc;
+
+///A strategy for escaping content.
+pub trait Escaper: Send + Sync {
+	///Write `s` to `out`, escaped.
+	fn escape(&self, s: &str, out: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+///How content is escaped when it's inserted into a template.
+///
+///Escaping only applies to placeholder content. Text from the template
+///itself, `Safe` content and nested templates and shells are written as they
+///are, since nested templates escape their own content.
+///
+///The escaping of a template can be overridden for a single placeholder
+///with the `escape` filter, like `[[:query|escape url]]`, using the names
+///`none`, `html`, `json`, `url`, `csv`, `shell` and `xml`.
+#[derive(Clone, Default)]
+pub enum Escaping {
+	///Write content as it is.
+	#[default]
+	None,
+	///Escape `&`, `<`, `>`, `"` and `'` as HTML entities.
+	Html,
+	///Escape content for use within a JSON string. The quotes around the
+	///string are not added.
+	Json,
+	///Percent encode everything except unreserved characters, for use as a
+	///URL component.
+	Url,
+	///Quote content as a CSV field, if it contains commas, quotes or line
+	///breaks, or starts or ends with whitespace.
+	Csv,
+	///Quote content as a single word for POSIX shells, if it contains
+	///anything other than letters, digits and `_@%+=:,./-`.
+	Shell,
+	///Escape content for use within an XML attribute value.
+	Xml,
+	///Escape content with a custom `Escaper`.
+	Custom(Arc<dyn Escaper>)
+}
+
+impl Escaping {
+	///The default escaping for templates with the file extension `extension`.
+	///
+	///Files with the extensions `html` and `htm` are escaped as HTML, and
+	///everything else isn't escaped.
+	pub fn for_extension(extension: &str) -> Escaping {
+		match &*extension.to_lowercase() {
+			"html" | "htm" => Escaping::Html,
+			_ => Escaping::None
+		}
+	}
+
+	///Find a built-in escaping by the name that is used in `escape` filters.
+	pub fn from_name(name: &str) -> Option<Escaping> {
+		match name {
+			"none" => Some(Escaping::None),
+			"html" => Some(Escaping::Html),
+			"json" => Some(Escaping::Json),
+			"url" => Some(Escaping::Url),
+			"csv" => Some(Escaping::Csv),
+			"shell" => Some(Escaping::Shell),
+			"xml" => Some(Escaping::Xml),
+			_ => None
+		}
+	}
+}
+
+impl Escaper for Escaping {
+	fn escape(&self, s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+		match *self {
+			Escaping::None => out.write_str(s),
+			Escaping::Html => replace(s, out, |c| match c {
+				'&' => Some("&amp;"),
+				'<' => Some("&lt;"),
+				'>' => Some("&gt;"),
+				'"' => Some("&quot;"),
+				'\'' => Some("&#39;"),
+				_ => None
+			}),
+			Escaping::Json => escape_json(s, out),
+			Escaping::Url => escape_url(s, out),
+			Escaping::Csv => escape_csv(s, out),
+			Escaping::Shell => escape_shell(s, out),
+			Escaping::Xml => replace(s, out, |c| match c {
+				'&' => Some("&amp;"),
+				'<' => Some("&lt;"),
+				'>' => Some("&gt;"),
+				'"' => Some("&quot;"),
+				'\'' => Some("&apos;"),
+				'\t' => Some("&#9;"),
+				'\n' => Some("&#10;"),
+				'\r' => Some("&#13;"),
+				_ => None
+			}),
+			Escaping::Custom(ref escaper) => escaper.escape(s, out)
+		}
+	}
+}
+
+impl PartialEq for Escaping {
+	fn eq(&self, other: &Escaping) -> bool {
+		match (self, other) {
+			(&Escaping::None, &Escaping::None) |
+			(&Escaping::Html, &Escaping::Html) |
+			(&Escaping::Json, &Escaping::Json) |
+			(&Escaping::Url, &Escaping::Url) |
+			(&Escaping::Csv, &Escaping::Csv) |
+			(&Escaping::Shell, &Escaping::Shell) |
+			(&Escaping::Xml, &Escaping::Xml) => true,
+			(Escaping::Custom(a), Escaping::Custom(b)) => Arc::ptr_eq(a, b),
+			_ => false
+		}
+	}
+}
+
+impl fmt::Debug for Escaping {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Escaping::None => "None",
+			Escaping::Html => "Html",
+			Escaping::Json => "Json",
+			Escaping::Url => "Url",
+			Escaping::Csv => "Csv",
+			Escaping::Shell => "Shell",
+			Escaping::Xml => "Xml",
+			Escaping::Custom(_) => "Custom(..)"
+		})
+	}
+}
+
+///Write `content` with `escaping`, unless it's safe.
+pub fn write_content(content: &ContentType, escaping: &Escaping, f: &mut fmt::Formatter) -> fmt::Result {
+	match *content {
+		ContentType::Safe(_) | ContentType::Template(_) | ContentType::Shell(_) => fmt::Display::fmt(content, f),
+		ContentType::Lazy(ref lazy) => lazy.with(|content| write_content(content, escaping, f)),
+		ContentType::List(ref items) => {
+			for item in items.iter() {
+				write_content(item, escaping, f)?;
+			}
+
+			Ok(())
+		},
+		_ => match *escaping {
+			Escaping::None => fmt::Display::fmt(content, f),
+			ref escaping => escaping.escape(&content.to_string(), f)
+		}
+	}
+}
+
+///Write `s`, where the characters that `replacement` returns something for are replaced.
+fn replace<F: Fn(char) -> Option<&'static str>>(s: &str, out: &mut dyn fmt::Write, replacement: F) -> fmt::Result {
+	let mut last = 0;
+
+	for (index, c) in s.char_indices() {
+		if let Some(replacement) = replacement(c) {
+			out.write_str(&s[last..index])?;
+			out.write_str(replacement)?;
+			last = index + c.len_utf8();
+		}
+	}
+
+	out.write_str(&s[last..])
+}
+
+fn escape_json(s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+	for c in s.chars() {
+		match c {
+			'"' => out.write_str("\\\""),
+			'\\' => out.write_str("\\\\"),
+			'\n' => out.write_str("\\n"),
+			'\r' => out.write_str("\\r"),
+			'\t' => out.write_str("\\t"),
+			'\u{8}' => out.write_str("\\b"),
+			'\u{c}' => out.write_str("\\f"),
+			c if c < ' ' || c == '\u{2028}' || c == '\u{2029}' => write!(out, "\\u{:04x}", c as u32),
+			c => out.write_char(c)
+		}?;
+	}
+
+	Ok(())
+}
+
+fn escape_url(s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+	for &byte in s.as_bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.write_char(byte as char),
+			byte => write!(out, "%{:02X}", byte)
+		}?;
+	}
+
+	Ok(())
+}
+
+fn escape_csv(s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+	let quoted = s.chars().any(|c| c == ',' || c == '"' || c == '\n' || c == '\r') ||
+		s.starts_with(char::is_whitespace) ||
+		s.ends_with(char::is_whitespace);
+
+	if !quoted {
+		return out.write_str(s);
+	}
+
+	out.write_char('"')?;
+	replace(s, out, |c| if c == '"' { Some("\"\"") } else { None })?;
+	out.write_char('"')
+}
+
+fn escape_shell(s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+	let plain = !s.is_empty() && s.chars().all(|c| matches!(c,
+		'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '@' | '%' | '+' | '=' | ':' | ',' | '.' | '/' | '-'
+	));
+
+	if plain {
+		return out.write_str(s);
+	}
+
+	out.write_char('\'')?;
+	replace(s, out, |c| if c == '\'' { Some("'\\''") } else { None })?;
+	out.write_char('\'')
+}
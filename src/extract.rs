@@ -0,0 +1,205 @@
+use super::{Token, Spanned, Catalog, line_of};
+use parser;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+///A translatable string that is found in templates.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Extracted {
+	///The message id, or the literal text.
+	pub id: String,
+	///`true` if any of the translation tokens has a `count` argument, so
+	///the message needs plural forms.
+	pub plural: bool,
+	///The distinct argument names of the translation tokens.
+	pub arguments: Vec<String>,
+	///Every place where the string is found, in the order they were found in.
+	pub references: Vec<Reference>
+}
+
+///A place in a template source file.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Reference {
+	///The name of the file.
+	pub file: String,
+	///The line, starting at 1.
+	pub line: usize
+}
+
+impl fmt::Display for Reference {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}", self.file, self.line)
+	}
+}
+
+///Collects the translatable strings in templates, to be written as a `.pot`
+///catalog for translators.
+///
+///Every translation token, like `[[~greeting name=:name]]`, becomes an
+///entry, with a reference to each file and line where it's used. Literal
+///text can be extracted as well, with `set_strings`, which is useful when
+///moving existing templates to translation tokens.
+///
+///The extractor is displayed as a `.pot` catalog, and the extracted ids
+///can also be compared with an existing catalog, to find entries that are
+///no longer used by any template.
+#[derive(Clone, Default, Debug)]
+pub struct Extractor {
+	messages: Vec<Extracted>,
+	strings: bool
+}
+
+impl Extractor {
+	///Create an `Extractor` for translation tokens only.
+	pub fn new() -> Extractor {
+		Extractor {
+			messages: Vec::new(),
+			strings: false
+		}
+	}
+
+	///Choose if literal text, outside of tags, should be extracted as well.
+	///Whitespace around the text is left out, and so is text without any
+	///letters.
+	pub fn set_strings(&mut self, strings: bool) {
+		self.strings = strings;
+	}
+
+	///Extract the strings from a template file.
+	pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+		let path = path.as_ref();
+		let source = fs::read_to_string(path).map_err(|e| format!("{}: io error: {}", path.display(), e))?;
+		self.add_source(&path.display().to_string(), &source)
+	}
+
+	///Parse the template source `source`, from the file `file`, and extract its strings.
+	pub fn add_source(&mut self, file: &str, source: &str) -> Result<(), String> {
+		let tokens = parser::parse(source.chars().map(Ok::<char, String>)).map_err(|e| {
+			let line = line_of(source, e.span.start);
+			format!("{}:{}: {}", file, line, e.node)
+		})?;
+
+		self.add_tokens(file, source, &tokens);
+		Ok(())
+	}
+
+	///Extract the strings from tokens that were parsed from `source`, in the file `file`.
+	///
+	///The spans of the tokens are used to find the lines in `source`.
+	pub fn add_tokens(&mut self, file: &str, source: &str, tokens: &[Spanned<Token>]) {
+		for token in tokens.iter() {
+			match token.node {
+				Token::Translated(ref id, ref args) => {
+					let extracted = self.entry(id, file, line_of(source, token.span.start));
+					extracted.plural |= args.iter().any(|arg| arg.name == "count");
+
+					for arg in args.iter() {
+						if !extracted.arguments.contains(&arg.name) {
+							extracted.arguments.push(arg.name.clone());
+						}
+					}
+				},
+				Token::String(ref s) if self.strings => {
+					let text = s.trim();
+
+					if text.chars().any(char::is_alphabetic) {
+						let leading = &s[..s.len() - s.trim_start().len()];
+						let line = line_of(source, token.span.start) + leading.matches('\n').count();
+						self.entry(text, file, line);
+					}
+				},
+				Token::Conditional(_, _, ref tokens) | Token::ContentConditional(_, _, ref tokens) => {
+					self.add_tokens(file, source, tokens);
+				},
+				_ => {}
+			}
+		}
+	}
+
+	///The extracted strings, in the order they were first found in.
+	pub fn messages(&self) -> &[Extracted] {
+		&self.messages
+	}
+
+	///The ids of the entries in `catalog` that aren't used by any of the
+	///templates, in sorted order.
+	pub fn stale<'a>(&self, catalog: &'a Catalog) -> Vec<&'a str> {
+		let used: Vec<&str> = self.messages.iter().map(|message| &*message.id).collect();
+		catalog.unused_ids(&used)
+	}
+
+	fn entry(&mut self, id: &str, file: &str, line: usize) -> &mut Extracted {
+		let index = match self.messages.iter().position(|message| message.id == id) {
+			Some(index) => index,
+			None => {
+				self.messages.push(Extracted {
+					id: id.to_owned(),
+					plural: false,
+					arguments: Vec::new(),
+					references: Vec::new()
+				});
+				self.messages.len() - 1
+			}
+		};
+
+		let extracted = &mut self.messages[index];
+		extracted.references.push(Reference {
+			file: file.to_owned(),
+			line
+		});
+		extracted
+	}
+}
+
+impl fmt::Display for Extractor {
+	///Writes the extracted strings as a `.pot` catalog. Each entry has its
+	///references and argument names as comments, and entries with a `count`
+	///argument get two plural forms.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n")?;
+
+		for message in self.messages.iter() {
+			f.write_str("\n")?;
+
+			if !message.arguments.is_empty() {
+				writeln!(f, "#. arguments: {}", message.arguments.join(", "))?;
+			}
+
+			f.write_str("#:")?;
+			for reference in message.references.iter() {
+				write!(f, " {}", reference)?;
+			}
+			f.write_str("\n")?;
+
+			write_po_line(f, "msgid", &message.id)?;
+
+			if message.plural {
+				write_po_line(f, "msgid_plural", &message.id)?;
+				f.write_str("msgstr[0] \"\"\nmsgstr[1] \"\"\n")?;
+			} else {
+				f.write_str("msgstr \"\"\n")?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+fn write_po_line(f: &mut fmt::Formatter, keyword: &str, text: &str) -> fmt::Result {
+	write!(f, "{} \"", keyword)?;
+
+	for c in text.chars() {
+		match c {
+			'\\' => f.write_str("\\\\")?,
+			'"' => f.write_str("\\\"")?,
+			'\n' => f.write_str("\\n")?,
+			'\t' => f.write_str("\\t")?,
+			'\r' => f.write_str("\\r")?,
+			c => fmt::Write::write_char(f, c)?
+		}
+	}
+
+	f.write_str("\"\n")
+}
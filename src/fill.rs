@@ -0,0 +1,120 @@
+use super::{ContentType, Template, Context, DateTime};
+use number;
+
+///Something that can be filled with content and conditions, like a `Template` or a `Context`.
+pub trait Fill<'c> {
+	///Insert content for the label `label`.
+	fn fill_content(&mut self, label: String, content: ContentType<'c>);
+
+	///Set the condition with the label `label`.
+	fn fill_condition(&mut self, label: String, value: bool);
+}
+
+impl<'c> Fill<'c> for Template<'c> {
+	fn fill_content(&mut self, label: String, content: ContentType<'c>) {
+		self.insert(label, content);
+	}
+
+	fn fill_condition(&mut self, label: String, value: bool) {
+		self.set(label, value);
+	}
+}
+
+impl<'c> Fill<'c> for Context<'c> {
+	fn fill_content(&mut self, label: String, content: ContentType<'c>) {
+		self.insert(label, content);
+	}
+
+	fn fill_condition(&mut self, label: String, value: bool) {
+		self.set(label, value);
+	}
+}
+
+///A type that provides the content and conditions of a template.
+///
+///It's usually implemented using `#[derive(TemplateContext)]`, which fills
+///the template with the fields of a struct. The name of a field is used as
+///label, and the values are converted using `TemplateField`. Fields can be
+///renamed with `#[template(rename = "label")]` and skipped with
+///`#[template(skip)]`.
+///
+///The derive can also check that the struct has a field for each label in a
+///template, when the program is compiled. The template is given as
+///`#[template(path = "templates/page.html")]`, relative to the crate's
+///`Cargo.toml`, or `#[template(source = "Hello, [[:name]]!")]`. Labels with
+///the name of a field as prefix, like `address.city`, are expected to be
+///provided by nested structs.
+pub trait TemplateContext {
+	///Fill `target` with content and conditions, where each label starts with `prefix`.
+	fn fill_context<'c, F: Fill<'c>>(&'c self, prefix: &str, target: &mut F);
+}
+
+///A value that can be used as a field in a `TemplateContext`.
+///
+///Booleans become conditions and other values become content. An `Option`
+///provides its value if it's `Some`, and nothing otherwise, so it can be
+///checked with `[[?:label]]`. A nested `TemplateContext` provides its labels
+///with the label of the field and a `.` as prefix, like `address.city`.
+pub trait TemplateField {
+	///Fill `target` with the value of the field, using the label `label`.
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F);
+}
+
+macro_rules! copy_field {
+	($variant:ident as $as_t:ty: $($t:ty),+) => {
+		$(impl TemplateField for $t {
+			fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+				target.fill_content(label.to_owned(), ContentType::$variant(*self as $as_t));
+			}
+		})+
+	}
+}
+
+copy_field!(Float as f64: f64);
+copy_field!(Int as i64: isize, i8, i16, i32, i64);
+copy_field!(UnsignedInt as u64: usize, u8, u16, u32, u64);
+copy_field!(Char as char: char);
+
+impl TemplateField for f32 {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		target.fill_content(label.to_owned(), ContentType::Float(number::widen(*self)));
+	}
+}
+
+impl TemplateField for DateTime {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		target.fill_content(label.to_owned(), ContentType::DateTime(*self));
+	}
+}
+
+impl TemplateField for bool {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		target.fill_condition(label.to_owned(), *self);
+	}
+}
+
+impl TemplateField for String {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		target.fill_content(label.to_owned(), ContentType::StringSlice(self));
+	}
+}
+
+impl TemplateField for &str {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		target.fill_content(label.to_owned(), ContentType::StringSlice(self));
+	}
+}
+
+impl<T: TemplateField> TemplateField for Option<T> {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		if let Some(ref value) = *self {
+			value.fill_field(label, target);
+		}
+	}
+}
+
+impl<T: TemplateField + ?Sized> TemplateField for Box<T> {
+	fn fill_field<'c, F: Fill<'c>>(&'c self, label: &str, target: &mut F) {
+		(**self).fill_field(label, target);
+	}
+}
@@ -0,0 +1,147 @@
+use super::{ContentType, Filter, Escaping, Locale, DateTime};
+use number::{self, SignificantDigits, ExponentFormat};
+use spec::{FormatSpec, Notation};
+use escape;
+
+use std::fmt;
+
+///Displays content through the filters of a placeholder.
+pub struct Filtered<'a, 'c: 'a> {
+	pub content: &'a ContentType<'c>,
+	pub filters: &'a [Filter],
+	pub escaping: &'a Escaping,
+	pub locale: Option<&'a Locale>,
+	pub now: Option<DateTime>
+}
+
+impl<'a, 'c> fmt::Display for Filtered<'a, 'c> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write_filtered(self.content, self.filters, self.escaping, self.locale, self.now, f)
+	}
+}
+
+///Write `content` through `filters`, where `escaping` is the escaping of
+///the template. The last `date` or `relative` filter turns dates into text,
+///where `relative` compares them with `now`, or the current time, and
+///then the last `format` filter formats the content, using `locale`, or the
+///default locale, for numbers, currencies and percentages, and the last
+///`escape` filter replaces the escaping.
+pub fn write_filtered(content: &ContentType, filters: &[Filter], escaping: &Escaping, locale: Option<&Locale>, now: Option<DateTime>, f: &mut fmt::Formatter) -> fmt::Result {
+	if let ContentType::Lazy(ref lazy) = *content {
+		return lazy.with(|content| write_filtered(content, filters, escaping, locale, now, f));
+	}
+
+	let custom = last_argument(filters, "escape").and_then(Escaping::from_name);
+	let escaping = custom.as_ref().unwrap_or(escaping);
+
+	let dated = filters.iter()
+		.rev()
+		.find(|filter| filter.name == "date" || filter.name == "relative")
+		.and_then(|filter| format_date(content, filter, now));
+	let content = dated.as_ref().unwrap_or(content);
+
+	match last_argument(filters, "format").and_then(|spec| FormatSpec::parse(spec).ok()) {
+		Some(spec) => {
+			let default;
+			let locale = match locale {
+				Some(locale) => locale,
+				None => {
+					default = Locale::default();
+					&default
+				}
+			};
+			let formatted = format_content(content, &spec, locale)?;
+			escape::write_content(&formatted, escaping, f)
+		},
+		None => escape::write_content(content, escaping, f)
+	}
+}
+
+fn last_argument<'a>(filters: &'a [Filter], name: &str) -> Option<&'a str> {
+	filters.iter()
+		.rev()
+		.filter(|filter| filter.name == name)
+		.filter_map(|filter| filter.args.first())
+		.map(|arg| &**arg)
+		.next()
+}
+
+///Format dates, and integers as Unix timestamps, with a `date` or `relative` filter.
+fn format_date(content: &ContentType, filter: &Filter, now: Option<DateTime>) -> Option<ContentType<'static>> {
+	let date = match *content {
+		ContentType::DateTime(date) => date,
+		ContentType::Int(timestamp) => DateTime::from_timestamp(timestamp, 0)?,
+		ContentType::UnsignedInt(timestamp) if timestamp <= i64::MAX as u64 => DateTime::from_timestamp(timestamp as i64, 0)?,
+		_ => return None
+	};
+
+	let text = match filter.args.first() {
+		_ if filter.name == "relative" => date.relative_to(&now.unwrap_or_else(DateTime::now)),
+		Some(pattern) => date.format(pattern).to_string(),
+		None => date.to_string()
+	};
+
+	Some(ContentType::String(text))
+}
+
+///Format `content` with `spec`. The result is safe if the content was safe.
+fn format_content(content: &ContentType, spec: &FormatSpec, locale: &Locale) -> Result<ContentType<'static>, fmt::Error> {
+	let mut formatted = String::new();
+	let number = match *content {
+		ContentType::Float(v) => Some(format_number(v, SignificantDigits::DigAll, ExponentFormat::ExpNone, spec, locale)),
+		ContentType::FormattedFloat(v, digits, exponent) => Some(format_number(v, digits, exponent, spec, locale)),
+		ContentType::Int(_) | ContentType::UnsignedInt(_) if spec.precision.is_none() && spec.notation.is_none() => {
+			Some(with_sign(content.to_string(), spec))
+		},
+		ContentType::Int(_) | ContentType::UnsignedInt(_) if spec.precision.is_none() && spec.notation == Some(Notation::Number) => {
+			Some(with_sign(locale.format_number(&content.to_string()), spec))
+		},
+		ContentType::Int(i) => Some(format_number(i as f64, SignificantDigits::DigAll, ExponentFormat::ExpNone, spec, locale)),
+		ContentType::UnsignedInt(u) => Some(format_number(u as f64, SignificantDigits::DigAll, ExponentFormat::ExpNone, spec, locale)),
+		_ => None
+	};
+
+	match number {
+		Some(number) => spec.pad(&number, true, &mut formatted)?,
+		None => {
+			let text = content.to_string();
+			match spec.precision {
+				Some(precision) => spec.pad(&text.chars().take(precision).collect::<String>(), false, &mut formatted)?,
+				None => spec.pad(&text, false, &mut formatted)?
+			}
+		}
+	}
+
+	Ok(match *content {
+		ContentType::Safe(_) | ContentType::Template(_) | ContentType::Shell(_) => ContentType::Safe(Box::new(ContentType::String(formatted))),
+		_ => ContentType::String(formatted)
+	})
+}
+
+///Format a number, where the precision and notation of `spec` replace
+///`digits` and `exponent`. Currencies have two decimals and percentages
+///none, unless the spec has a precision.
+fn format_number(value: f64, digits: SignificantDigits, exponent: ExponentFormat, spec: &FormatSpec, locale: &Locale) -> String {
+	let exact = |default| SignificantDigits::DigExact(spec.precision.unwrap_or(default));
+	let digits = spec.precision.map_or(digits, SignificantDigits::DigExact);
+
+	let formatted = match spec.notation {
+		Some(Notation::Fixed) => number::format_float(value, digits, ExponentFormat::ExpNone),
+		Some(Notation::Exponent) => number::format_float(value, digits, ExponentFormat::ExpDec),
+		Some(Notation::BinaryExponent) => number::format_float(value, digits, ExponentFormat::ExpBin),
+		Some(Notation::Number) => locale.format_number(&number::format_float(value, digits, ExponentFormat::ExpNone)),
+		Some(Notation::Currency) => locale.format_currency(&number::format_float(value, exact(2), ExponentFormat::ExpNone)),
+		Some(Notation::Percent) => locale.format_percent(&number::format_float(value * 100.0, exact(0), ExponentFormat::ExpNone)),
+		None => number::format_float(value, digits, exponent)
+	};
+
+	with_sign(formatted, spec)
+}
+
+fn with_sign(number: String, spec: &FormatSpec) -> String {
+	if spec.sign && !number.starts_with('-') && number != "NaN" {
+		format!("+{}", number)
+	} else {
+		number
+	}
+}
@@ -0,0 +1,110 @@
+use libc;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+const EVENTS: u32 = libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_CLOSE_WRITE | libc::IN_MOVED_FROM |
+	libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF;
+
+///A non-blocking inotify instance that watches directories for changes.
+pub struct Inotify {
+	fd: c_int,
+	watched: HashMap<c_int, PathBuf>
+}
+
+impl Inotify {
+	///Create a new inotify instance, or `None` if it's not available.
+	pub fn new() -> Option<Inotify> {
+		let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+
+		if fd < 0 {
+			None
+		} else {
+			Some(Inotify {
+				fd,
+				watched: HashMap::new()
+			})
+		}
+	}
+
+	///Watch the files in `directory`, unless it's already watched.
+	///Returns `false` if the directory couldn't be watched.
+	pub fn watch(&mut self, directory: &Path) -> bool {
+		if self.watched.values().any(|watched| watched == directory) {
+			return true;
+		}
+
+		let path = match CString::new(directory.as_os_str().as_bytes()) {
+			Ok(path) => path,
+			Err(_) => return false
+		};
+
+		let descriptor = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), EVENTS) };
+
+		if descriptor < 0 {
+			false
+		} else {
+			self.watched.insert(descriptor, directory.to_path_buf());
+			true
+		}
+	}
+
+	///Forget the watched directories that are not in `directories`, so that
+	///they can be watched again if they are recreated. The kernel removes the
+	///watches when the directories are deleted.
+	pub fn retain(&mut self, directories: &[PathBuf]) {
+		self.watched.retain(|_, directory| directories.contains(directory));
+	}
+
+	///Read every pending event, and return the paths they are about. The
+	///result is `None` if there were no events.
+	pub fn read_events(&mut self) -> Option<HashSet<PathBuf>> {
+		let mut buffer = [0u8; 4096];
+		let mut paths = HashSet::new();
+		let mut found = false;
+
+		loop {
+			let length = unsafe { libc::read(self.fd, buffer.as_mut_ptr() as *mut c_void, buffer.len()) };
+
+			if length <= 0 {
+				return if found { Some(paths) } else { None };
+			}
+
+			found = true;
+			let length = length as usize;
+			let mut offset = 0;
+
+			while offset + mem::size_of::<libc::inotify_event>() <= length {
+				let event: libc::inotify_event = unsafe { ptr::read_unaligned(buffer.as_ptr().add(offset) as *const _) };
+				let name_start = offset + mem::size_of::<libc::inotify_event>();
+				let name_end = (name_start + event.len as usize).min(length);
+				offset = name_end;
+
+				if let Some(directory) = self.watched.get(&event.wd) {
+					//the name is padded with null bytes
+					let name = &buffer[name_start..name_end];
+					let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+
+					if name.is_empty() {
+						paths.insert(directory.clone());
+					} else {
+						paths.insert(directory.join(OsStr::from_bytes(name)));
+					}
+				}
+			}
+		}
+	}
+}
+
+impl Drop for Inotify {
+	fn drop(&mut self) {
+		unsafe {
+			libc::close(self.fd);
+		}
+	}
+}
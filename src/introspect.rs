@@ -0,0 +1,134 @@
+use super::{Token, Span, Spanned};
+
+///A label that is used in a template, together with every place where it's used.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Usage {
+	///The label.
+	pub label: String,
+	///Every place where the label is used, in template order.
+	pub occurrences: Vec<Occurrence>
+}
+
+///A place in a template where a label is used.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Occurrence {
+	///The position of the token in the token tree, as the index in each
+	///level, starting from the top level list and ending with the token itself.
+	pub path: Vec<usize>,
+	///The location of the token in the template source.
+	pub span: Span,
+	///The conditional blocks around the token, from the outermost to the innermost.
+	pub blocks: Vec<Block>
+}
+
+impl Occurrence {
+	///The number of conditional blocks around the token.
+	#[inline]
+	pub fn depth(&self) -> usize {
+		self.blocks.len()
+	}
+}
+
+///A conditional block around a token.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Block {
+	///The label of the condition or placeholder that the block depends on.
+	pub label: String,
+	///The value that makes the content visible.
+	pub expected: bool,
+	///`true` if it depends on whether a placeholder has content, like `[[?:label]]`.
+	pub content_condition: bool
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+	Placeholder,
+	Condition,
+	ContentCondition,
+	Generator,
+	Include,
+	Translation
+}
+
+///Find the distinct labels of one kind, in the order they first appear in.
+pub fn find_usages(tokens: &[Spanned<Token>], kind: Kind) -> Vec<Usage> {
+	let mut usages = Vec::new();
+	collect(tokens, kind, &mut Vec::new(), &mut Vec::new(), &mut usages);
+	usages
+}
+
+fn collect(tokens: &[Spanned<Token>], kind: Kind, path: &mut Vec<usize>, blocks: &mut Vec<Block>, usages: &mut Vec<Usage>) {
+	for (index, token) in tokens.iter().enumerate() {
+		path.push(index);
+
+		match &token.node {
+			&Token::String(_) => {},
+
+			&Token::Placeholder(ref k) | &Token::Filtered(ref k, _) => if kind == Kind::Placeholder {
+				add(usages, k, path, token.span, blocks);
+			},
+
+			&Token::Conditional(ref k, expected, ref tokens) => {
+				if kind == Kind::Condition {
+					add(usages, k, path, token.span, blocks);
+				}
+
+				blocks.push(Block {
+					label: k.clone(),
+					expected,
+					content_condition: false
+				});
+				collect(tokens, kind, path, blocks, usages);
+				blocks.pop();
+			},
+
+			&Token::ContentConditional(ref k, expected, ref tokens) => {
+				if kind == Kind::ContentCondition {
+					add(usages, k, path, token.span, blocks);
+				}
+
+				blocks.push(Block {
+					label: k.clone(),
+					expected,
+					content_condition: true
+				});
+				collect(tokens, kind, path, blocks, usages);
+				blocks.pop();
+			},
+
+			Token::Generated(k, _) => if kind == Kind::Generator {
+				add(usages, k, path, token.span, blocks);
+			},
+
+			Token::Include(k) => if kind == Kind::Include {
+				add(usages, k, path, token.span, blocks);
+			},
+
+			Token::Translated(id, args) => if kind == Kind::Placeholder {
+				for label in args.iter().filter_map(|arg| arg.label()) {
+					add(usages, label, path, token.span, blocks);
+				}
+			} else if kind == Kind::Translation {
+				add(usages, id, path, token.span, blocks);
+			}
+		}
+
+		path.pop();
+	}
+}
+
+fn add(usages: &mut Vec<Usage>, label: &str, path: &[usize], span: Span, blocks: &[Block]) {
+	let occurrence = Occurrence {
+		path: path.to_vec(),
+		span,
+		blocks: blocks.to_vec()
+	};
+
+	match usages.iter().position(|usage| usage.label == label) {
+		Some(index) => usages[index].occurrences.push(occurrence),
+		None => usages.push(Usage {
+			label: label.to_owned(),
+			occurrences: vec![occurrence]
+		})
+	}
+}
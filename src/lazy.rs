@@ -0,0 +1,128 @@
+use super::{ContentType, TemplateContent};
+
+use std::cell::Cell;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_RENDER: AtomicUsize = AtomicUsize::new(1);
+
+thread_local!(static RENDER: Cell<(usize, usize)> = const { Cell::new((0, 0)) });
+
+///Produced content, together with the rendering it was produced in.
+type Cache = Mutex<Option<(usize, Box<ContentType<'static>>)>>;
+
+///Content that is produced when a placeholder is rendered.
+///
+///The function of a `Lazy` is only called if a placeholder actually
+///reaches it, so expensive content within a rarely true condition costs
+///nothing most of the time. The content is a `ContentType<'static>`, which
+///means that it's owned, but the function itself may borrow.
+///
+///A memoized `Lazy` calls its function at most once per rendering, even if
+///the content is used by more than one placeholder, and again for the next
+///rendering. Conditions, paths and specialization don't look into lazy
+///content.
+pub struct Lazy<'c> {
+	evaluate: Box<dyn Fn() -> ContentType<'static> + Send + Sync + 'c>,
+	cache: Option<Cache>
+}
+
+impl<'c> Lazy<'c> {
+	///Create a `Lazy` that calls `evaluate` each time it's rendered.
+	pub fn new<F, T>(evaluate: F) -> Lazy<'c> where
+		F: Fn() -> T + Send + Sync + 'c,
+		T: TemplateContent<'static>
+	{
+		Lazy {
+			evaluate: Box::new(move || evaluate().into_template_content()),
+			cache: None
+		}
+	}
+
+	///Create a `Lazy` that calls `evaluate` at most once per rendering.
+	pub fn memoized<F, T>(evaluate: F) -> Lazy<'c> where
+		F: Fn() -> T + Send + Sync + 'c,
+		T: TemplateContent<'static>
+	{
+		Lazy {
+			evaluate: Box::new(move || evaluate().into_template_content()),
+			cache: Some(Mutex::new(None))
+		}
+	}
+
+	///Produce the content and pass it to `use_content`. A memoized `Lazy`
+	///reuses its content if it's already produced in the current rendering.
+	pub fn with<R, F: FnOnce(&ContentType) -> R>(&self, use_content: F) -> R {
+		let render = current_render();
+
+		if let (Some(cache), true) = (self.cache.as_ref(), render != 0) {
+			//The cache is busy if the content is used while it's produced.
+			if let Ok(mut cached) = cache.try_lock() {
+				let fresh = match *cached {
+					Some((id, _)) => id != render,
+					None => true
+				};
+
+				if fresh {
+					*cached = Some((render, Box::new((self.evaluate)())));
+				}
+
+				if let Some((_, ref content)) = *cached {
+					return use_content(content);
+				}
+			}
+		}
+
+		use_content(&(self.evaluate)())
+	}
+}
+
+impl<'c> fmt::Debug for Lazy<'c> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Lazy")
+			.field("memoized", &self.cache.is_some())
+			.finish()
+	}
+}
+
+impl<'c> TemplateContent<'c> for Lazy<'c> {
+	fn into_template_content(self) -> ContentType<'c> {
+		ContentType::Lazy(self)
+	}
+}
+
+///Marks the current thread as rendering, while it exists. Nested scopes,
+///like included templates, belong to the same rendering.
+pub struct RenderScope;
+
+impl RenderScope {
+	pub fn enter() -> RenderScope {
+		RENDER.with(|render| {
+			let (depth, id) = render.get();
+			let id = if depth == 0 {
+				NEXT_RENDER.fetch_add(1, Ordering::Relaxed)
+			} else {
+				id
+			};
+			render.set((depth + 1, id));
+		});
+
+		RenderScope
+	}
+}
+
+impl Drop for RenderScope {
+	fn drop(&mut self) {
+		RENDER.with(|render| {
+			let (depth, id) = render.get();
+			let id = if depth <= 1 { 0 } else { id };
+			render.set((depth.saturating_sub(1), id));
+		});
+	}
+}
+
+///The id of the current rendering, or 0 if nothing is rendered.
+fn current_render() -> usize {
+	RENDER.with(|render| render.get().1)
+}
@@ -1,34 +1,82 @@
 #![doc(html_root_url = "http://ogeon.github.io/fragments/doc/")]
-#![feature(std_misc, io)]
+
+extern crate fragments_macros;
+extern crate fragments_syntax;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(target_os = "linux")]
+extern crate libc;
+
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(test)]
+extern crate self as fragments;
 
 use std::fmt;
 use std::str::FromStr;
 use std::io::BufRead;
 use std::vec::Vec;
 use std::collections::{HashMap, HashSet};
-use std::num::strconv::{
-	float_to_str_common,
-	SignFormat
-};
-
-pub use std::num::strconv::{
-	SignificantDigits,
-	ExponentFormat
-};
-
-mod parser;
-
-///Internal representation of template parts.
-#[derive(PartialEq, Debug)]
-pub enum Token {
-	String(String),
-	Placeholder(String),
-	Conditional(String, bool, Vec<Token>),
-	ContentConditional(String, bool, Vec<Token>),
-	Generated(String, Vec<String>)
-}
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub use fragments_syntax::{Token, Filter, Argument, ArgumentValue, Span, Spanned};
+pub use fragments_macros::{template, include_template, TemplateContext};
+pub use fill::{Fill, TemplateContext, TemplateField};
+pub use program::{Program, Instruction};
+pub use symbols::{Symbols, Label};
+pub use introspect::{Usage, Occurrence, Block};
+pub use check::{Report, Suggestion, DefinedLabels};
+pub use visit::{Visitor, VisitorMut, Fold};
+pub use loader::{TemplateSet, Loader, LoadError, Watcher};
+pub use escape::{Escaping, Escaper};
+pub use number::{SignificantDigits, ExponentFormat};
+pub use locale::Locale;
+pub use date::DateTime;
+pub use catalog::{Catalog, Translations};
+pub use plural::PluralCategory;
+pub use extract::{Extractor, Extracted, Reference};
+pub use lazy::Lazy;
+pub use resolver::{Resolver, Environment};
+
+use introspect::Kind;
+use fragments_syntax::{parser, source, spec};
+
+mod program;
+mod symbols;
+mod introspect;
+mod check;
+mod specialize;
+mod loader;
+mod fill;
+mod escape;
+mod filter;
+mod number;
+mod locale;
+mod date;
+mod catalog;
+mod plural;
+mod extract;
+mod lazy;
+mod resolver;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(target_os = "linux")]
+mod inotify;
+pub mod visit;
 
 ///Container enum for template content
+///
+///A `Map` holds named content, which can be reached with paths like
+///`[[:user.address.city]]`, and a `List` renders its items after each other.
+///Maps render nothing on their own. `Safe` content is already escaped, and
+///is written as it is, even in templates with escaping.
+///
+///A `Shell` is stored as the content it displays, since it borrows the
+///template below it. `Lazy` content is produced when a placeholder reaches
+///it.
 pub enum ContentType<'c> {
 	Float(f64),
 	FormattedFloat(f64, SignificantDigits, ExponentFormat),
@@ -36,15 +84,43 @@ pub enum ContentType<'c> {
 	UnsignedInt(u64),
 	Char(char),
 	Bool(bool),
+	DateTime(DateTime),
 	String(String),
 	StringSlice(&'c str),
 	Template(Template<'c>),
-	Shell(Shell<'c, 'c>),
-	Fmt(Box<fmt::Display + Send + Sync + 'c>)
+	Shell(Box<dyn fmt::Display + Send + Sync + 'c>),
+	Fmt(Box<dyn fmt::Display + Send + Sync + 'c>),
+	List(Vec<ContentType<'c>>),
+	Map(HashMap<String, ContentType<'c>>),
+	Safe(Box<ContentType<'c>>),
+	Lazy(Lazy<'c>)
+}
+
+impl<'c> ContentType<'c> {
+	///Get the content at a path, like `address.city`, within this content.
+	///
+	///Each part of the path is either a key in a `Map` or an index in a
+	///`List`. An empty path is the content itself.
+	pub fn get_path<'a>(&'a self, path: &str) -> Option<&'a ContentType<'c>> {
+		if path.is_empty() {
+			return Some(self);
+		}
+
+		let mut content = self;
+		for key in path.split('.') {
+			content = match *content {
+				ContentType::Map(ref entries) => entries.get(key)?,
+				ContentType::List(ref items) => key.parse::<usize>().ok().and_then(|index| items.get(index))?,
+				_ => return None
+			};
+		}
+
+		Some(content)
+	}
 }
 
 macro_rules! call_fmt {
-	($slf:ident, $f:ident: $($p:pat => $b:expr),+ and $($t:ident),+) => {
+	($slf:ident, $f:ident: $($p:pat => $b:expr),+; $($t:ident),+) => {
 		match $slf {
 			$($p => $b,)+
 			$(&ContentType::$t(ref v) => fmt::Display::fmt(v, $f)),+
@@ -57,22 +133,25 @@ impl<'c> fmt::Display for ContentType<'c> {
 		call_fmt! {
 			self,
 			f:
-			&ContentType::FormattedFloat(v, sig, exp) => {
-				let (string, _special) = float_to_str_common(v, 10, false, SignFormat::SignNeg, sig, exp, false);
-				fmt::Display::fmt(&string, f)
+			&ContentType::FormattedFloat(v, sig, exp) => fmt::Display::fmt(&number::format_float(v, sig, exp), f),
+			ContentType::Fmt(v) => fmt::Display::fmt(&**v, f),
+			ContentType::List(items) => {
+				for item in items.iter() {
+					fmt::Display::fmt(item, f)?;
+				}
+
+				Ok(())
 			},
-			&ContentType::Fmt(ref v) => {
-				use std::fmt::Display;
-				(**v).fmt(f)
-			}
-			
-			and
+			&ContentType::Map(_) => Ok(()),
+			ContentType::Safe(v) => fmt::Display::fmt(&**v, f),
+			ContentType::Lazy(v) => v.with(|content| fmt::Display::fmt(content, f));
 
 			Int,
 			UnsignedInt,
 			Float,
 			Char,
 			Bool,
+			DateTime,
 			String,
 			StringSlice,
 			Template,
@@ -88,16 +167,6 @@ pub trait TemplateContent<'c> {
 	fn into_template_content(self) -> ContentType<'c>;
 }
 
-macro_rules! float_content {
-	($($t:ty),+) => {
-		$(impl TemplateContent<'static> for $t {
-			fn into_template_content(self) -> ContentType<'static> {
-				ContentType::Float(self as f64)
-			}
-		})+
-	}
-}
-
 macro_rules! int_content {
 	($($t:ty),+) => {
 		$(impl TemplateContent<'static> for $t {
@@ -128,10 +197,21 @@ macro_rules! deref_content {
 	}
 }
 
-float_content!(f32, f64);
 int_content!(isize, i8, i16, i32, i64);
 uint_content!(usize, u8, u16, u32, u64);
-deref_content!([char, Char], [bool, Bool]);
+deref_content!([f64, Float], [char, Char], [bool, Bool], [DateTime, DateTime]);
+
+impl TemplateContent<'static> for f32 {
+	fn into_template_content(self) -> ContentType<'static> {
+		ContentType::Float(number::widen(self))
+	}
+}
+
+impl TemplateContent<'static> for SystemTime {
+	fn into_template_content(self) -> ContentType<'static> {
+		ContentType::DateTime(DateTime::from_system_time(self))
+	}
+}
 
 
 impl TemplateContent<'static> for String {
@@ -157,18 +237,44 @@ impl<'c> TemplateContent<'c> for Template<'c> {
 
 impl<'r, 'c: 'r> TemplateContent<'r> for Shell<'r, 'c> {
 	fn into_template_content(self) -> ContentType<'r> {
-		ContentType::Shell(self)
+		ContentType::Shell(Box::new(self))
 	}
 }
 
 
-impl<'c> TemplateContent<'c> for Box<fmt::Display + Send + Sync + 'c> {
+impl<'c> TemplateContent<'c> for Box<dyn fmt::Display + Send + Sync + 'c> {
 	fn into_template_content(self) -> ContentType<'c> {
 		ContentType::Fmt(self)
 	}
 }
 
 
+impl<'c, T: TemplateContent<'c>> TemplateContent<'c> for Vec<T> {
+	fn into_template_content(self) -> ContentType<'c> {
+		ContentType::List(self.into_iter().map(|item| item.into_template_content()).collect())
+	}
+}
+
+
+impl<'c, T: TemplateContent<'c>> TemplateContent<'c> for HashMap<String, T> {
+	fn into_template_content(self) -> ContentType<'c> {
+		ContentType::Map(self.into_iter().map(|(key, item)| (key, item.into_template_content())).collect())
+	}
+}
+
+
+///Content that is already escaped, like a snippet of HTML.
+///
+///It's written as it is, even in templates with escaping.
+pub struct Safe<T>(pub T);
+
+impl<'c, T: TemplateContent<'c>> TemplateContent<'c> for Safe<T> {
+	fn into_template_content(self) -> ContentType<'c> {
+		ContentType::Safe(Box::new(self.0.into_template_content()))
+	}
+}
+
+
 impl<'c> TemplateContent<'c> for ContentType<'c> {
 	fn into_template_content(self) -> ContentType<'c> {
 		self
@@ -189,11 +295,22 @@ impl<'c> TemplateContent<'c> for ContentType<'c> {
 ///is missing from the `conditions` set. Conditional segments can also depend on whether a placeholder has an assigned value. 
 ///Just write them like this: `[[?:label]]...[[/]]` or `[[?!:label]]...[[/]]`.
 ///
+///The content of a placeholder can be formatted with a format spec after the label, like `[[:price:.2]]` for
+///two decimals, `[[:id:>8]]` for right alignment in 8 characters or `[[:ratio:e3]]` for an exponent and
+///three decimals. It may contain a fill character, alignment, sign, width, precision and notation.
+///
 ///Content can also be generated, using a generator token: `[[+label arg1 arg2 ...]]`. The label and the arguments are
 ///separated by one or more whitespaces. They can also be quoted to prevent special characters from being parsed:
 ///`[[+"my label" arg1 "[[arg2]]"]]`. The arguments will be passed to an instance of the `Generator` trait and the
 ///result will be inserted into the content.
 ///
+///Translated messages are written with a translation token: `[[~greeting name=:name title="Dr."]]`. The message id
+///is looked up in the `Translations` of the template, in the languages from `set_languages`, and each argument is
+///either the content of a placeholder or a literal text. See `Catalog` for how messages are written.
+///
+///Labels without content or conditions can be resolved on demand by a `Resolver`, which is set with
+///`set_resolver`. It may read them from a cache, environment variables or a configuration file.
+///
 ///Any character can be escaped by writing `\` before it. It can be used like this: `\[[[:label1]], [[:label2]]]`
 ///which will result in `[content1, content2]`, since the first `[` will be ignored by the parser and added to the
 ///rest of the content.
@@ -201,40 +318,46 @@ pub struct Template<'c> {
 	///Content for the placeholders
 	pub content: HashMap<String, ContentType<'c>>,
 	///Content generators
-	pub generators: HashMap<String, Box<Generator + 'c>>,
+	pub generators: HashMap<String, Box<dyn Generator + 'c>>,
 	///Conditional switches
 	pub conditions: HashSet<String>,
-	tokens: Vec<Token>
+	template: Arc<CompiledTemplate>,
+	locale: Option<Box<Locale>>,
+	now: Option<DateTime>,
+	translations: Option<Arc<Translations>>,
+	languages: Vec<String>,
+	resolver: Option<Box<dyn Resolver + 'c>>
 }
 
 impl<'c> Template<'c> {
 	///Create a new `Template` from a character iterator.
 	#[inline]
 	pub fn from_chars(b: std::str::Chars) -> Result<Template<'c>, String> {
-		let tokens = try!(parser::parse(b.map(|r| Ok::<char, String>(r))));
-
-		Ok(Template {
-			content: HashMap::new(),
-			generators: HashMap::new(),
-			conditions: HashSet::new(),
-			tokens: tokens
-		})
+		let template = CompiledTemplate::from_chars(b)?;
+		Ok(Template::from_compiled(Arc::new(template)))
 	}
 
 	///Create a new `Template` from a buffer.
 	#[inline]
 	pub fn from_buffer<T: BufRead>(b: T) -> Result<Template<'c>, String> {
-		let tokens = try!(parser::parse(b.chars().map(|r| match r {
-			Ok(c) => Ok(c),
-			Err(e) => Err(format!("io error: {}", e))
-		})));
+		let template = CompiledTemplate::from_buffer(b)?;
+		Ok(Template::from_compiled(Arc::new(template)))
+	}
 
-		Ok(Template {
+	///Create a new `Template` around a shared `CompiledTemplate`.
+	#[inline]
+	pub fn from_compiled(template: Arc<CompiledTemplate>) -> Template<'c> {
+		Template {
 			content: HashMap::new(),
 			generators: HashMap::new(),
 			conditions: HashSet::new(),
-			tokens: tokens
-		})
+			template,
+			locale: None,
+			now: None,
+			translations: None,
+			languages: Vec::new(),
+			resolver: None
+		}
 	}
 
 	///Insert content.
@@ -252,7 +375,23 @@ impl<'c> Template<'c> {
 	///Insert a content generator.
 	#[inline]
 	pub fn insert_generator<T: Generator + 'c>(&mut self, label: String, gen: T) {
-		self.generators.insert(label, Box::new(gen) as Box<Generator>);
+		self.generators.insert(label, Box::new(gen) as Box<dyn Generator>);
+	}
+
+	///Fill the template with the content and conditions from `context`.
+	#[inline]
+	pub fn fill<C: TemplateContext>(&mut self, context: &'c C) {
+		context.fill_context("", self);
+	}
+
+	///Fill the template with the content and conditions from a serializable value.
+	///
+	///The value has to be a map or a struct. Booleans become conditions,
+	///sequences become lists and nested maps and structs provide labels like
+	///`address.city`. This requires the `serde` feature.
+	#[cfg(feature = "serde")]
+	pub fn fill_serialized<T: serde::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), String> {
+		serialize::fill(value, self)
 	}
 
 	///Set a condition.
@@ -265,300 +404,1282 @@ impl<'c> Template<'c> {
 		}
 	}
 
-	///Create a `Shell` around this `Template`.
+	///Set the locale for numbers, currencies and percentages in format specs.
 	#[inline]
-	pub fn wrap<'a: 'c>(&'a self) -> Shell<'a, 'c> {
-		Shell::new(self)
+	pub fn set_locale(&mut self, locale: Locale) {
+		self.locale = Some(Box::new(locale));
 	}
 
-	pub fn get_tokens(&self) -> &[Token] {
-		&*self.tokens
+	///Set the time that the `relative` filter compares dates with, instead
+	///of the time of rendering.
+	#[inline]
+	pub fn set_now(&mut self, now: DateTime) {
+		self.now = Some(now);
 	}
-}
 
-impl<'c> InnerTemplate<'c> for Template<'c> {
-	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'c>> {
-		self.content.get(label)
+	///Set the message catalogs for translation tokens.
+	#[inline]
+	pub fn set_translations(&mut self, translations: Arc<Translations>) {
+		self.translations = Some(translations);
 	}
 
-	fn get_condition(&self, label: &str) -> bool {
-		self.conditions.contains(label)
-	}
-	
-	fn is_content_defined(&self, label: &str) -> bool {
-		self.content.contains_key(label)
+	///Set the languages for translation tokens, like `["de-AT", "en"]`, in
+	///the order they are tried in.
+	#[inline]
+	pub fn set_languages(&mut self, languages: &[&str]) {
+		self.languages = languages.iter().map(|&language| language.to_owned()).collect();
 	}
 
-	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a Generator> {
-		self.generators.get(label).map(|v| &**v)
+	///Set the fallback for labels that have no content or conditions.
+	#[inline]
+	pub fn set_resolver<R: Resolver + 'c>(&mut self, resolver: R) {
+		self.resolver = Some(Box::new(resolver));
 	}
 
-	fn render(&self, top_template: &InnerTemplate, f: &mut fmt::Formatter) -> fmt::Result {
-		format_tokens(top_template, &self.tokens, f)
+	///Create a `Shell` around this `Template`.
+	#[inline]
+	pub fn wrap<'a>(&'a self) -> Shell<'a, 'c> {
+		Shell::new(self)
 	}
-}
 
-impl<'c> FromStr for Template<'c> {
-	type Err = String;
-	
-	///Creates a new `Template` from a string.
-	fn from_str(s: &str) -> Result<Template<'c>, String> {
-		Template::from_chars(s.chars())
+	pub fn get_tokens(&self) -> &[Spanned<Token>] {
+		self.template.get_tokens()
 	}
-}
 
-impl<'c> fmt::Display for Template<'c> {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		format_tokens(self as &InnerTemplate, &self.tokens, f)
+	///Get the shared `CompiledTemplate` of this `Template`.
+	pub fn get_compiled(&self) -> &Arc<CompiledTemplate> {
+		&self.template
 	}
-}
-
-
 
+	///Set the escaping that is used for placeholder content.
+	///
+	///See `Escaping` for what is escaped.
+	pub fn set_escaping(&mut self, escaping: Escaping) {
+		Arc::make_mut(&mut self.template).set_escaping(escaping);
+	}
 
+	///Compile the tokens into a flat `Program` and use it for rendering.
+	///
+	///The `CompiledTemplate` will be copied first if it's shared with other templates.
+	pub fn compile(&mut self) {
+		Arc::make_mut(&mut self.template).compile();
+	}
 
-///A `Shell` is a layer on top of an overridable template.
-///
-///Shells can be used to assign different values to a template and still
-///keep the original intact.
-pub struct Shell<'r, 'c: 'r> {
-    ///Content for the placeholders
-	pub content: HashMap<String, Option<ContentType<'r>>>,
-	///Content generators
-	pub generators: HashMap<String, Option<Box<Generator + 'r>>>,
-	///Conditional switches
-	pub conditions: HashMap<String, bool>,
-    base: &'r (InnerTemplate<'c> + 'r)
-}
+	///Get the compiled `Program`, if the template has been compiled.
+	pub fn get_program(&self) -> Option<&Program> {
+		self.template.get_program()
+	}
 
-impl<'r, 'c> Shell<'r, 'c> {
-	///Create a new `Shell` around `base`.
-	pub fn new<T: InnerTemplate<'c>>(base: &'r T) -> Shell<'r, 'c> {
-		Shell {
-			content: HashMap::new(),
-			generators: HashMap::new(),
-			conditions: HashMap::new(),
-			base: base as &InnerTemplate<'c>
-		}
+	///List the distinct placeholder labels that are used in the template.
+	#[inline]
+	pub fn placeholders(&self) -> Vec<Usage> {
+		self.template.placeholders()
 	}
 
-	///Insert content.
+	///List the distinct condition labels that are used in the template.
 	#[inline]
-	pub fn insert<T: TemplateContent<'c>>(&mut self, label: String, item: T) {
-		self.content.insert(label, Some(item.into_template_content()));
+	pub fn conditions(&self) -> Vec<Usage> {
+		self.template.conditions()
 	}
 
-	///Unset content.
+	///List the distinct labels that are used in content conditions, like `[[?:label]]`.
 	#[inline]
-	pub fn unset(&mut self, label: String) {
-		self.content.insert(label, None);
+	pub fn content_conditions(&self) -> Vec<Usage> {
+		self.template.content_conditions()
 	}
 
-	///Insert a formatted float.
+	///List the distinct generator labels that are used in the template.
 	#[inline]
-	pub fn insert_formatted_float(&mut self, label: String, item: f64, precision: SignificantDigits, exponent: ExponentFormat) {
-		self.content.insert(label, Some(ContentType::FormattedFloat(item, precision, exponent)));
+	pub fn generators(&self) -> Vec<Usage> {
+		self.template.generators()
 	}
 
-	///Insert a content generator.
+	///List the distinct names of the templates that are included by the template.
 	#[inline]
-	pub fn insert_generator<T: Generator + Send + 'c>(&mut self, label: String, gen: T) {
-		self.generators.insert(label, Some(Box::new(gen) as Box<Generator>));
+	pub fn includes(&self) -> Vec<Usage> {
+		self.template.includes()
 	}
 
-	///Unset a content generator.
+	///List the distinct message ids of the translation tokens in the template.
 	#[inline]
-	pub fn unset_generator(&mut self, label: String) {
-		self.generators.insert(label, None);
+	pub fn translations(&self) -> Vec<Usage> {
+		self.template.translations()
 	}
 
-	///Set a condition.
+	///Check for missing and unused content, conditions and generators.
 	#[inline]
-	pub fn set(&mut self, label: String, value: bool) {
-		self.conditions.insert(label, value);
+	pub fn check(&self) -> Report {
+		check::check(self.get_tokens(), self)
 	}
 
-	///Create an other `Shell` around this `Shell`.
+	///Write the tokens of the template as template source.
 	#[inline]
-	pub fn wrap<'a: 'c>(&'a self) -> Shell<'a, 'c> {
-		Shell::new(self)
+	pub fn to_source(&self) -> String {
+		self.template.to_source()
+	}
+
+	///Replace the tokens of the template with the result of `folder`.
+	///
+	///The template will get its own `CompiledTemplate`, so other templates
+	///that share the current one are not affected.
+	pub fn transform<F: Fold>(&mut self, folder: &mut F) {
+		self.template = Arc::new(self.template.fold(folder));
+	}
+
+	///Create a new template where the parts that are known from `context`
+	///are folded away, as described for `CompiledTemplate::specialize`.
+	///
+	///The new template starts without any content of its own, so only the
+	///parts that weren't known have to be provided when it's rendered.
+	pub fn specialize<'n>(&self, context: &dyn Lookup) -> Template<'n> {
+		Template::from_compiled(Arc::new(self.template.specialize(context)))
+	}
+
+	///Walk through the tokens of the template and change them in place.
+	///
+	///The `CompiledTemplate` will be copied first if it's shared with other templates.
+	pub fn visit_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+		Arc::make_mut(&mut self.template).visit_mut(visitor);
 	}
 }
 
-impl<'r, 'c: 'r> InnerTemplate<'r> for Shell<'r, 'c> {
-	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'r>> {
-		match self.content.get(label) {
-			Some(&Some(ref v)) => Some(v),
-			Some(&None) => None,
-			None => self.base.get_content(label).map(|v| &*v)
-		}
+impl<'c> Lookup<'c> for Template<'c> {
+	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'c>> {
+		self.content.get(label).or_else(|| {
+			find_path(label, |prefix| self.content.get(prefix).map(Some)).and_then(|content| content)
+		})
 	}
 
 	fn get_condition(&self, label: &str) -> bool {
-		self.conditions.get(label).map(|&v| v).unwrap_or_else(|| self.base.get_condition(label))
+		self.conditions.contains(label) || is_true(find_path(label, |prefix| self.content.get(prefix).map(Some)))
 	}
 	
 	fn is_content_defined(&self, label: &str) -> bool {
-		match self.content.get(label) {
-			Some(&Some(_)) => true,
-			Some(&None) => false,
-			None => self.base.is_content_defined(label)
-		}
+		self.get_content(label).is_some()
 	}
 
-	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a Generator> {
-		match self.generators.get(label) {
-			Some(&Some(ref v)) => Some(&**v),
-			Some(&None) => None,
-			None => self.base.get_generator(label).map(|v| &*v)
-		}
+	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a dyn Generator> {
+		self.generators.get(label).map(|v| &**v)
 	}
 
-	fn render(&self, top_template: &InnerTemplate, f: &mut fmt::Formatter) -> fmt::Result {
-		self.base.render(top_template, f)
+	fn collect_labels(&self, labels: &mut DefinedLabels) {
+		labels.content.extend(self.content.keys().cloned());
+		labels.conditions.extend(self.conditions.iter().map(|k| (k.clone(), true)));
+		labels.generators.extend(self.generators.keys().cloned());
 	}
-}
 
-impl<'r, 'c> fmt::Display for Shell<'r, 'c> {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		self.base.render(self as &InnerTemplate, f)
+	fn get_locale(&self) -> Option<&Locale> {
+		self.locale.as_deref()
 	}
-}
 
+	fn get_now(&self) -> Option<DateTime> {
+		self.now
+	}
 
+	fn get_translations(&self) -> Option<&Translations> {
+		self.translations.as_deref()
+	}
 
+	fn get_languages(&self) -> &[String] {
+		&self.languages
+	}
 
-///A trait for overridable templates.
-pub trait InnerTemplate<'c>: Send + Sync {
-	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'c>>;
-	fn get_condition(&self, label: &str) -> bool;
-	fn is_content_defined(&self, label: &str) -> bool;
-	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a Generator>;
-	fn render(&self, top_template: &InnerTemplate, f: &mut fmt::Formatter) -> fmt::Result;
+	fn get_resolver(&self, _label: &str) -> Option<&dyn Resolver> {
+		self.resolver.as_deref()
+	}
 }
 
+impl<'c> InnerTemplate<'c> for Template<'c> {
+	fn get_tokens(&self) -> &[Spanned<Token>] {
+		self.template.get_tokens()
+	}
 
-///A trait for content generators.
-pub trait Generator: Send + Sync {
-	fn generate(&self, args: &[String], formatter:  &mut fmt::Formatter) -> fmt::Result;
+	fn render(&self, top_template: &dyn Lookup, f: &mut fmt::Formatter) -> fmt::Result {
+		self.template.render_with(top_template, f)
+	}
 }
 
-impl<F: Send + Sync + Fn(&[String], & mut fmt::Formatter) -> fmt::Result> Generator for F {
-	fn generate(&self, args: &[String], formatter:  &mut fmt::Formatter) -> fmt::Result {
-		(*self)(args, formatter)
+impl<'c> FromStr for Template<'c> {
+	type Err = String;
+	
+	///Creates a new `Template` from a string.
+	fn from_str(s: &str) -> Result<Template<'c>, String> {
+		Template::from_chars(s.chars())
 	}
 }
 
+impl<'c> fmt::Display for Template<'c> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.template.render_with(self as &dyn Lookup, f)
+	}
+}
 
 
 
-fn format_tokens(template: &InnerTemplate, tokens: &[Token], f: &mut fmt::Formatter) -> fmt::Result {
-	for token in tokens.iter() {
-		let res = match token {
-			&Token::String(ref s) => f.write_str(s),
 
-			&Token::Placeholder(ref k) => {
-				match template.get_content(k) {
-					Some(value) => fmt::Display::fmt(value, f),
-					None => Ok(())
-				}
-			},
 
-			&Token::Conditional(ref k, expected, ref tokens) => {
-				if template.get_condition(k) == expected {
-					format_tokens(template, &tokens, f)
-				} else {
-					Ok(())
-				}
-			},
+///The parsed tokens of a template, without any content.
+///
+///A `CompiledTemplate` doesn't change while it's rendered, so it can be shared
+///between threads and renderings, for example by putting it in an `Arc`. The
+///content is instead provided by a `Context`, or any other `Lookup`, when it's
+///rendered: `compiled.render(&context)`.
+#[derive(Clone, Debug)]
+pub struct CompiledTemplate {
+	tokens: Vec<Spanned<Token>>,
+	symbols: Arc<Symbols>,
+	program: Option<Program>,
+	escaping: Escaping
+}
 
-			&Token::ContentConditional(ref k, expected, ref tokens) => {
-				if template.is_content_defined(k) == expected {
-					format_tokens(template, &tokens, f)
-				} else {
-					Ok(())
-				}
-			},
+impl CompiledTemplate {
+	///Create a new `CompiledTemplate` from a character iterator. Syntax
+	///errors start with the line where they are, like `line 3: ...`.
+	#[inline]
+	pub fn from_chars(b: std::str::Chars) -> Result<CompiledTemplate, String> {
+		let source = b.as_str();
+		let tokens = parser::parse(b.map(Ok::<char, String>)).map_err(|e| {
+			format!("line {}: {}", line_of(source, e.span.start), e.node)
+		})?;
+		Ok(CompiledTemplate::from_tokens(tokens))
+	}
 
-			&Token::Generated(ref k, ref vars) => {
-				match template.get_generator(k) {
-					Some(gen) => gen.generate(&vars, f),
-					None => Ok(())
-				}
-			}
-		};
+	///Create a new `CompiledTemplate` from a buffer.
+	#[inline]
+	pub fn from_buffer<T: BufRead>(mut b: T) -> Result<CompiledTemplate, String> {
+		let mut source = String::new();
+		b.read_to_string(&mut source).map_err(|e| format!("io error: {}", e))?;
+		CompiledTemplate::from_chars(source.chars())
+	}
 
-		match res {
-			Err(e) => return Err(e),
-			_ => {}
+	///Create a new `CompiledTemplate` from already parsed tokens.
+	#[inline]
+	pub fn from_tokens(tokens: Vec<Spanned<Token>>) -> CompiledTemplate {
+		CompiledTemplate {
+			symbols: Arc::new(Symbols::from_tokens(&tokens)),
+			tokens,
+			program: None,
+			escaping: Escaping::None
 		}
 	}
 
-	Ok(())
-}
-
+	///The parsed tokens, together with their byte ranges in the template source.
+	pub fn get_tokens(&self) -> &[Spanned<Token>] {
+		&self.tokens
+	}
 
+	///Get the symbol table with every label that is used in the template.
+	pub fn get_symbols(&self) -> &Arc<Symbols> {
+		&self.symbols
+	}
 
+	///Resolve `name` to an interned `Label`, if it's used in the template.
+	#[inline]
+	pub fn label(&self, name: &str) -> Option<Label> {
+		self.symbols.get(name)
+	}
 
-#[cfg(test)]
-mod test {
-	use super::{Template, Token, SignificantDigits, ExponentFormat};
-	use std::fmt;
-	use std::borrow::ToOwned;
+	///Compile the tokens into a flat `Program` and use it for rendering.
+	///
+	///Compiled templates resolve each label at most once per rendering,
+	///which makes them faster to render when labels are used many times.
+	///Contexts created with `Context::for_template` are looked up by slot,
+	///without hashing the labels at all.
+	pub fn compile(&mut self) {
+		self.program = Some(Program::compile_with(&self.tokens, self.symbols.clone()));
+	}
 
-	macro_rules! test_insert {
-		($($v:expr),+) => {
-			#[test]
-			fn test_insert() {
-				let mut template: Template = monitored_from_str("[[:v]]");
-				$(
-					template.insert("v".to_owned(), $v);
-					assert_eq!(template.to_string(), $v.to_string());
-				)+
-			}
-		}
+	///Get the compiled `Program`, if the template has been compiled.
+	pub fn get_program(&self) -> Option<&Program> {
+		self.program.as_ref()
 	}
 
-	static PETER: &'static str = "Peter";
-	static NICE: &'static str = "nice";
+	///List the distinct placeholder labels that are used in the template.
+	///
+	///The labels are listed in the order they first appear in, together
+	///with the position and the surrounding conditional blocks of each use.
+	pub fn placeholders(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::Placeholder)
+	}
 
-	fn monitored_from_str(s: &str) -> Template {
-		match Template::from_chars(s.chars()) {
-			Ok(template) => template,
-			Err(e) => panic!(e)
-		}
+	///List the distinct condition labels that are used in the template.
+	pub fn conditions(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::Condition)
 	}
 
-	fn echo(parts: &[String], f: &mut fmt::Formatter) -> fmt::Result {
-		fmt::Display::fmt(&parts.connect(":"), f)
+	///List the distinct labels that are used in content conditions, like `[[?:label]]`.
+	pub fn content_conditions(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::ContentCondition)
 	}
 
-	fn echo2(parts: &[String], f: &mut fmt::Formatter) -> fmt::Result {
-		fmt::Display::fmt(&parts.connect("_"), f)
+	///List the distinct generator labels that are used in the template.
+	pub fn generators(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::Generator)
 	}
 
-	#[test]
-	fn basic_tokens() {
-		let template: Template = "Hello, [[:name]]! This is a [[:something]] template.".parse().unwrap();
-		assert_eq!(template.tokens[0], Token::String("Hello, ".to_owned()));
-		assert_eq!(template.tokens[1], Token::Placeholder("name".to_owned()));
-		assert_eq!(template.tokens[2], Token::String("! This is a ".to_owned()));
-		assert_eq!(template.tokens[3], Token::Placeholder("something".to_owned()));
-		assert_eq!(template.tokens[4], Token::String(" template.".to_owned()));
+	///List the distinct names of the templates that are included by the template.
+	pub fn includes(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::Include)
 	}
 
-	#[test]
-	#[should_panic]
-	fn strange_tokens() {
-		let _: Template = "Hello, [[[:name]]]! This is a [[[[:something]] template.".parse().unwrap();
+	///List the distinct message ids of the translation tokens in the template.
+	pub fn translations(&self) -> Vec<Usage> {
+		introspect::find_usages(&self.tokens, Kind::Translation)
 	}
 
-	#[test]
-	fn escaped_tokens() {
-		let template = monitored_from_str("Hello, [[:name]]! Write placeholders like \\[[:this]] and escape them like \\\\\\[[:this]]");
-		assert_eq!(template.tokens[0], Token::String("Hello, ".to_owned()));
-		assert_eq!(template.tokens[1], Token::Placeholder("name".to_owned()));
-		assert_eq!(template.tokens[2], Token::String("! Write placeholders like [[:this]] and escape them like \\[[:this]]".to_owned()));
+	///Check `context` for missing and unused content, conditions and generators.
+	pub fn check(&self, context: &dyn Lookup) -> Report {
+		check::check(&self.tokens, context)
+	}
+
+	///Create a new `CompiledTemplate` from the result of folding the tokens with `folder`.
+	///
+	///The new template is compiled if this one is.
+	pub fn fold<F: Fold>(&self, folder: &mut F) -> CompiledTemplate {
+		let mut template = CompiledTemplate::from_tokens(folder.fold_tokens(self.tokens.clone()));
+		template.escaping = self.escaping.clone();
+
+		if self.program.is_some() {
+			template.compile();
+		}
+
+		template
+	}
+
+	///Create a new `CompiledTemplate` where the parts that are known from
+	///`context` are folded away.
+	///
+	///Conditions that are set in `context` are replaced by their content or
+	///removed, as are content conditions for content in `context`.
+	///Placeholders with static content, like numbers and strings, become
+	///plain text, while nested templates, shells and `Fmt` values stay as
+	///placeholders. Everything else is left as it is, to be provided when the
+	///new template is rendered. The new template is compiled if this one is.
+	pub fn specialize(&self, context: &dyn Lookup) -> CompiledTemplate {
+		self.fold(&mut specialize::Specializer::new(context, &self.escaping))
+	}
+
+	///Walk through the tokens and change them in place.
+	///
+	///The symbol table and the compiled program are updated afterwards.
+	pub fn visit_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+		visitor.visit_tokens_mut(&mut self.tokens);
+		self.symbols = Arc::new(Symbols::from_tokens(&self.tokens));
+
+		if self.program.is_some() {
+			self.compile();
+		}
+	}
+
+	///Write the tokens as template source.
+	///
+	///The result can be parsed into the same tokens again, but it may be
+	///escaped and quoted differently than the original source.
+	pub fn to_source(&self) -> String {
+		struct Source<'a>(&'a [Spanned<Token>]);
+
+		impl<'a> fmt::Display for Source<'a> {
+			fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				source::write_tokens(self.0, f)
+			}
+		}
+
+		Source(&self.tokens).to_string()
+	}
+
+	///Render the template with content from `context`.
+	///
+	///The result can be displayed or turned into a `String`.
+	#[inline]
+	pub fn render<'a, 'c>(&'a self, context: &'a (dyn Lookup<'c> + 'a)) -> Render<'a, 'c> {
+		Render {
+			template: self,
+			context
+		}
+	}
+
+	///The escaping that is used for placeholder content.
+	pub fn get_escaping(&self) -> &Escaping {
+		&self.escaping
+	}
+
+	///Set the escaping that is used for placeholder content.
+	pub fn set_escaping(&mut self, escaping: Escaping) {
+		self.escaping = escaping;
+	}
+
+	fn render_with(&self, top_template: &dyn Lookup, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.program {
+			Some(ref program) => program.render_escaped(top_template, &self.escaping, f),
+			None => format_tokens(top_template, &self.tokens, &self.escaping, f)
+		}
+	}
+}
+
+impl FromStr for CompiledTemplate {
+	type Err = String;
+
+	///Creates a new `CompiledTemplate` from a string.
+	fn from_str(s: &str) -> Result<CompiledTemplate, String> {
+		CompiledTemplate::from_chars(s.chars())
+	}
+}
+
+
+
+///A `CompiledTemplate` together with the content it's rendered with.
+pub struct Render<'a, 'c: 'a> {
+	template: &'a CompiledTemplate,
+	context: &'a (dyn Lookup<'c> + 'a)
+}
+
+impl<'a, 'c> fmt::Display for Render<'a, 'c> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.template.render_with(self.context, f)
+	}
+}
+
+
+
+///Content, conditions and generators for rendering a `CompiledTemplate`.
+///
+///A `Context` works like the content part of a `Template`, without any
+///tokens of its own, so the same `CompiledTemplate` can be rendered with
+///many different contexts.
+///
+///A context that is created with `Context::for_template` shares the symbol
+///table of the template and stores content and conditions for its labels in
+///slots. These can be set and read directly, using `Label`s from
+///`CompiledTemplate::label`, to avoid hashing the labels.
+pub struct Context<'c> {
+	///Content for the placeholders
+	pub content: HashMap<String, ContentType<'c>>,
+	///Content generators
+	pub generators: HashMap<String, Box<dyn Generator + 'c>>,
+	///Conditional switches
+	pub conditions: HashSet<String>,
+	symbols: Option<Arc<Symbols>>,
+	slots: Vec<Option<ContentType<'c>>>,
+	condition_slots: Vec<bool>,
+	locale: Option<Box<Locale>>,
+	now: Option<DateTime>,
+	translations: Option<Arc<Translations>>,
+	languages: Vec<String>,
+	resolver: Option<Box<dyn Resolver + 'c>>
+}
+
+impl<'c> Default for Context<'c> {
+	fn default() -> Context<'c> {
+		Context::new()
+	}
+}
+
+impl<'c> Context<'c> {
+	///Create a new, empty `Context`.
+	pub fn new() -> Context<'c> {
+		Context {
+			content: HashMap::new(),
+			generators: HashMap::new(),
+			conditions: HashSet::new(),
+			symbols: None,
+			slots: Vec::new(),
+			condition_slots: Vec::new(),
+			locale: None,
+			now: None,
+			translations: None,
+			languages: Vec::new(),
+			resolver: None
+		}
+	}
+
+	///Create a new, empty `Context` with slots for the labels of `template`.
+	pub fn for_template(template: &CompiledTemplate) -> Context<'c> {
+		let symbols = template.get_symbols().clone();
+		let len = symbols.len();
+
+		Context {
+			content: HashMap::new(),
+			generators: HashMap::new(),
+			conditions: HashSet::new(),
+			symbols: Some(symbols),
+			slots: (0..len).map(|_| None).collect(),
+			condition_slots: vec![false; len],
+			locale: None,
+			now: None,
+			translations: None,
+			languages: Vec::new(),
+			resolver: None
+		}
+	}
+
+	///Insert content.
+	#[inline]
+	pub fn insert<T: TemplateContent<'c>>(&mut self, label: String, item: T) {
+		match self.slot(&label) {
+			Some(label) => self.slots[label.index()] = Some(item.into_template_content()),
+			None => {
+				self.content.insert(label, item.into_template_content());
+			}
+		}
+	}
+
+	///Insert content into the slot of an interned label.
+	///
+	///This will panic if `label` doesn't belong to the template of this context.
+	#[inline]
+	pub fn insert_at<T: TemplateContent<'c>>(&mut self, label: Label, item: T) {
+		self.check_label(label);
+		self.slots[label.index()] = Some(item.into_template_content());
+	}
+
+	///Get the content of an interned label.
+	#[inline]
+	pub fn get_at(&self, label: Label) -> Option<&ContentType<'c>> {
+		match self.symbols.as_ref().and_then(|symbols| symbols.name(label)) {
+			Some(name) => self.get_content_at(label, name),
+			None => None
+		}
+	}
+
+	///Insert a formatted float.
+	#[inline]
+	pub fn insert_formatted_float(&mut self, label: String, item: f64, precision: SignificantDigits, exponent: ExponentFormat) {
+		self.insert(label, ContentType::FormattedFloat(item, precision, exponent));
+	}
+
+	///Insert a content generator.
+	#[inline]
+	pub fn insert_generator<T: Generator + 'c>(&mut self, label: String, gen: T) {
+		self.generators.insert(label, Box::new(gen) as Box<dyn Generator>);
+	}
+
+	///Fill the context with the content and conditions from `context`.
+	#[inline]
+	pub fn fill<C: TemplateContext>(&mut self, context: &'c C) {
+		context.fill_context("", self);
+	}
+
+	///Fill the context with the content and conditions from a serializable value.
+	///
+	///See `Template::fill_serialized` for how the value is used. This
+	///requires the `serde` feature.
+	#[cfg(feature = "serde")]
+	pub fn fill_serialized<T: serde::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), String> {
+		serialize::fill(value, self)
+	}
+
+	///Set a condition.
+	#[inline]
+	pub fn set(&mut self, label: String, value: bool) {
+		match self.slot(&label) {
+			Some(slot) => self.condition_slots[slot.index()] = value,
+			None => if value {
+				self.conditions.insert(label);
+			} else {
+				self.conditions.remove(&label);
+			}
+		}
+	}
+
+	///Set the locale for numbers, currencies and percentages in format specs.
+	#[inline]
+	pub fn set_locale(&mut self, locale: Locale) {
+		self.locale = Some(Box::new(locale));
+	}
+
+	///Set the time that the `relative` filter compares dates with, instead
+	///of the time of rendering.
+	#[inline]
+	pub fn set_now(&mut self, now: DateTime) {
+		self.now = Some(now);
+	}
+
+	///Set the message catalogs for translation tokens.
+	#[inline]
+	pub fn set_translations(&mut self, translations: Arc<Translations>) {
+		self.translations = Some(translations);
+	}
+
+	///Set the languages for translation tokens, like `["de-AT", "en"]`, in
+	///the order they are tried in.
+	#[inline]
+	pub fn set_languages(&mut self, languages: &[&str]) {
+		self.languages = languages.iter().map(|&language| language.to_owned()).collect();
+	}
+
+	///Set the fallback for labels that have no content or conditions.
+	#[inline]
+	pub fn set_resolver<R: Resolver + 'c>(&mut self, resolver: R) {
+		self.resolver = Some(Box::new(resolver));
+	}
+
+	///Set the condition of an interned label.
+	///
+	///This will panic if `label` doesn't belong to the template of this context.
+	#[inline]
+	pub fn set_at(&mut self, label: Label, value: bool) {
+		self.check_label(label);
+		self.condition_slots[label.index()] = value;
+	}
+
+	#[inline]
+	fn slot(&self, label: &str) -> Option<Label> {
+		self.symbols.as_ref().and_then(|symbols| symbols.get(label))
+	}
+
+	#[inline]
+	fn owns(&self, label: Label) -> bool {
+		self.symbols.as_ref().map(|symbols| symbols.owns(label)).unwrap_or(false)
+	}
+
+	fn check_label(&self, label: Label) {
+		if !self.owns(label) {
+			panic!("the label doesn't belong to the template of this context");
+		}
+	}
+
+	fn find_path(&self, label: &str) -> Option<Option<&ContentType<'c>>> {
+		find_path(label, |prefix| {
+			let slot = match self.slot(prefix) {
+				Some(slot) => self.slots[slot.index()].as_ref(),
+				None => None
+			};
+
+			slot.or_else(|| self.content.get(prefix)).map(Some)
+		})
+	}
+}
+
+impl<'c> Lookup<'c> for Context<'c> {
+	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'c>> {
+		match self.slot(label) {
+			Some(slot) => self.get_content_at(slot, label),
+			None => self.content.get(label).or_else(|| self.find_path(label).and_then(|content| content))
+		}
+	}
+
+	fn get_condition(&self, label: &str) -> bool {
+		match self.slot(label) {
+			Some(slot) => self.get_condition_at(slot, label),
+			None => self.conditions.contains(label) || is_true(self.find_path(label))
+		}
+	}
+
+	fn is_content_defined(&self, label: &str) -> bool {
+		self.get_content(label).is_some()
+	}
+
+	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a dyn Generator> {
+		self.generators.get(label).map(|v| &**v)
+	}
+
+	fn get_content_at<'a>(&'a self, label: Label, name: &str) -> Option<&'a ContentType<'c>> {
+		let slot = if self.owns(label) {
+			self.slots[label.index()].as_ref()
+		} else {
+			None
+		};
+
+		slot.or_else(|| self.content.get(name))
+			.or_else(|| self.find_path(name).and_then(|content| content))
+	}
+
+	fn get_condition_at(&self, label: Label, name: &str) -> bool {
+		let slot = if self.owns(label) {
+			Some(label)
+		} else {
+			self.slot(name)
+		};
+
+		let value = match slot {
+			Some(slot) => self.condition_slots[slot.index()],
+			None => self.conditions.contains(name)
+		};
+
+		value || is_true(self.find_path(name))
+	}
+
+	fn is_content_defined_at(&self, label: Label, name: &str) -> bool {
+		self.get_content_at(label, name).is_some()
+	}
+
+	fn collect_labels(&self, labels: &mut DefinedLabels) {
+		labels.content.extend(self.content.keys().cloned());
+		labels.conditions.extend(self.conditions.iter().map(|k| (k.clone(), true)));
+		labels.generators.extend(self.generators.keys().cloned());
+
+		if let Some(ref symbols) = self.symbols {
+			for (name, slot) in symbols.labels().iter().zip(self.slots.iter()) {
+				if slot.is_some() {
+					labels.content.insert(name.clone());
+				}
+			}
+
+			for (name, &value) in symbols.labels().iter().zip(self.condition_slots.iter()) {
+				if value {
+					labels.conditions.insert(name.clone(), true);
+				}
+			}
+		}
+	}
+
+	fn get_locale(&self) -> Option<&Locale> {
+		self.locale.as_deref()
+	}
+
+	fn get_now(&self) -> Option<DateTime> {
+		self.now
+	}
+
+	fn get_translations(&self) -> Option<&Translations> {
+		self.translations.as_deref()
+	}
+
+	fn get_languages(&self) -> &[String] {
+		&self.languages
+	}
+
+	fn get_resolver(&self, _label: &str) -> Option<&dyn Resolver> {
+		self.resolver.as_deref()
+	}
+}
+
+
+
+
+
+///A `Shell` is a layer on top of an overridable template.
+///
+///Shells can be used to assign different values to a template and still
+///keep the original intact.
+pub struct Shell<'r, 'c: 'r> {
+    ///Content for the placeholders
+	pub content: HashMap<String, Option<ContentType<'r>>>,
+	///Content generators
+	pub generators: HashMap<String, Option<Box<dyn Generator + 'r>>>,
+	///Conditional switches
+	pub conditions: HashMap<String, bool>,
+    base: &'r (dyn InnerTemplate<'c> + 'r),
+	locale: Option<Box<Locale>>,
+	now: Option<DateTime>,
+	translations: Option<Arc<Translations>>,
+	languages: Option<Vec<String>>,
+	resolver: Option<Box<dyn Resolver + 'r>>
+}
+
+impl<'r, 'c> Shell<'r, 'c> {
+	///Create a new `Shell` around `base`.
+	pub fn new<T: InnerTemplate<'c>>(base: &'r T) -> Shell<'r, 'c> {
+		Shell {
+			content: HashMap::new(),
+			generators: HashMap::new(),
+			conditions: HashMap::new(),
+			base: base as &dyn InnerTemplate<'c>,
+			locale: None,
+			now: None,
+			translations: None,
+			languages: None,
+			resolver: None
+		}
+	}
+
+	///Insert content.
+	#[inline]
+	pub fn insert<T: TemplateContent<'c>>(&mut self, label: String, item: T) {
+		self.content.insert(label, Some(item.into_template_content()));
+	}
+
+	///Unset content.
+	#[inline]
+	pub fn unset(&mut self, label: String) {
+		self.content.insert(label, None);
+	}
+
+	///Insert a formatted float.
+	#[inline]
+	pub fn insert_formatted_float(&mut self, label: String, item: f64, precision: SignificantDigits, exponent: ExponentFormat) {
+		self.content.insert(label, Some(ContentType::FormattedFloat(item, precision, exponent)));
+	}
+
+	///Insert a content generator.
+	#[inline]
+	pub fn insert_generator<T: Generator + Send + 'c>(&mut self, label: String, gen: T) {
+		self.generators.insert(label, Some(Box::new(gen) as Box<dyn Generator>));
+	}
+
+	///Unset a content generator.
+	#[inline]
+	pub fn unset_generator(&mut self, label: String) {
+		self.generators.insert(label, None);
+	}
+
+	///Set a condition.
+	#[inline]
+	pub fn set(&mut self, label: String, value: bool) {
+		self.conditions.insert(label, value);
+	}
+
+	///Set the locale for numbers, currencies and percentages in format
+	///specs, instead of the locale of the layers below.
+	#[inline]
+	pub fn set_locale(&mut self, locale: Locale) {
+		self.locale = Some(Box::new(locale));
+	}
+
+	///Set the time that the `relative` filter compares dates with, instead
+	///of the time of the layers below.
+	#[inline]
+	pub fn set_now(&mut self, now: DateTime) {
+		self.now = Some(now);
+	}
+
+	///Set the message catalogs for translation tokens, instead of the
+	///catalogs of the layers below.
+	#[inline]
+	pub fn set_translations(&mut self, translations: Arc<Translations>) {
+		self.translations = Some(translations);
+	}
+
+	///Set the languages for translation tokens, like `["de-AT", "en"]`,
+	///instead of the languages of the layers below.
+	#[inline]
+	pub fn set_languages(&mut self, languages: &[&str]) {
+		self.languages = Some(languages.iter().map(|&language| language.to_owned()).collect());
+	}
+
+	///Set the fallback for labels that have no content or conditions in
+	///any of the layers, instead of the fallback of the layers below. Labels
+	///that are set or unset in this layer are never resolved.
+	#[inline]
+	pub fn set_resolver<R: Resolver + 'r>(&mut self, resolver: R) {
+		self.resolver = Some(Box::new(resolver));
+	}
+
+	///Create an other `Shell` around this `Shell`.
+	#[inline]
+	pub fn wrap<'a>(&'a self) -> Shell<'a, 'r> {
+		Shell::new(self)
+	}
+
+	///Check for missing and unused content, conditions and generators in
+	///this `Shell` and all of the layers below it.
+	#[inline]
+	pub fn check(&self) -> Report {
+		check::check(self.base.get_tokens(), self)
+	}
+
+	///Find content in this layer. The result is `None` if the layer doesn't
+	///have any content for the label or its path, and `Some(None)` if it's
+	///unset or not found in the layer's content.
+	fn find(&self, label: &str) -> Option<Option<&ContentType<'r>>> {
+		match self.content.get(label) {
+			Some(content) => Some(content.as_ref()),
+			None => find_path(label, |prefix| self.content.get(prefix).map(Option::as_ref))
+		}
+	}
+}
+
+impl<'r, 'c: 'r> Lookup<'r> for Shell<'r, 'c> {
+	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'r>> {
+		match self.find(label) {
+			Some(content) => content,
+			None => self.base.get_content(label)
+		}
+	}
+
+	fn get_condition(&self, label: &str) -> bool {
+		match self.conditions.get(label) {
+			Some(&value) => value,
+			None => match find_path(label, |prefix| self.content.get(prefix).map(Option::as_ref)) {
+				Some(content) => is_true(Some(content)),
+				None => self.base.get_condition(label)
+			}
+		}
+	}
+	
+	fn is_content_defined(&self, label: &str) -> bool {
+		match self.find(label) {
+			Some(content) => content.is_some(),
+			None => self.base.is_content_defined(label)
+		}
+	}
+
+	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a dyn Generator> {
+		match self.generators.get(label) {
+			Some(Some(v)) => Some(&**v),
+			Some(&None) => None,
+			None => self.base.get_generator(label)
+		}
+	}
+
+	fn get_content_at<'a>(&'a self, label: Label, name: &str) -> Option<&'a ContentType<'r>> {
+		match self.find(name) {
+			Some(content) => content,
+			None => self.base.get_content_at(label, name)
+		}
+	}
+
+	fn get_condition_at(&self, label: Label, name: &str) -> bool {
+		match self.conditions.get(name) {
+			Some(&value) => value,
+			None => match find_path(name, |prefix| self.content.get(prefix).map(Option::as_ref)) {
+				Some(content) => is_true(Some(content)),
+				None => self.base.get_condition_at(label, name)
+			}
+		}
+	}
+
+	fn is_content_defined_at(&self, label: Label, name: &str) -> bool {
+		match self.find(name) {
+			Some(content) => content.is_some(),
+			None => self.base.is_content_defined_at(label, name)
+		}
+	}
+
+	fn collect_labels(&self, labels: &mut DefinedLabels) {
+		self.base.collect_labels(labels);
+
+		for (k, v) in self.content.iter() {
+			if v.is_some() {
+				labels.content.insert(k.clone());
+			} else {
+				labels.content.remove(k);
+			}
+		}
+
+		for (k, &v) in self.conditions.iter() {
+			labels.conditions.insert(k.clone(), v);
+		}
+
+		for (k, v) in self.generators.iter() {
+			if v.is_some() {
+				labels.generators.insert(k.clone());
+			} else {
+				labels.generators.remove(k);
+			}
+		}
+	}
+
+	fn get_locale(&self) -> Option<&Locale> {
+		self.locale.as_deref().or_else(|| self.base.get_locale())
+	}
+
+	fn get_now(&self) -> Option<DateTime> {
+		self.now.or_else(|| self.base.get_now())
+	}
+
+	fn get_translations(&self) -> Option<&Translations> {
+		self.translations.as_deref().or_else(|| self.base.get_translations())
+	}
+
+	fn get_languages(&self) -> &[String] {
+		match self.languages {
+			Some(ref languages) => languages,
+			None => self.base.get_languages()
+		}
+	}
+
+	fn get_resolver(&self, label: &str) -> Option<&dyn Resolver> {
+		if self.conditions.contains_key(label) || self.find(label).is_some() {
+			return None;
+		}
+
+		match self.resolver {
+			Some(ref resolver) => Some(&**resolver),
+			None => self.base.get_resolver(label)
+		}
+	}
+}
+
+impl<'r, 'c: 'r> InnerTemplate<'r> for Shell<'r, 'c> {
+	fn get_tokens(&self) -> &[Spanned<Token>] {
+		self.base.get_tokens()
+	}
+
+	fn render(&self, top_template: &dyn Lookup, f: &mut fmt::Formatter) -> fmt::Result {
+		self.base.render(top_template, f)
+	}
+}
+
+impl<'r, 'c> fmt::Display for Shell<'r, 'c> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.base.render(self as &dyn Lookup, f)
+	}
+}
+
+
+
+
+///A trait for types that provide content, conditions and generators for rendering.
+///
+///The `*_at` methods are used by compiled templates, which know the interned
+///`Label` as well as the name of each label. They fall back to the name by default.
+pub trait Lookup<'c>: Send + Sync {
+	fn get_content<'a>(&'a self, label: &str) -> Option<&'a ContentType<'c>>;
+	fn get_condition(&self, label: &str) -> bool;
+	fn is_content_defined(&self, label: &str) -> bool;
+	fn get_generator<'a>(&'a self, label: &str) -> Option<&'a dyn Generator>;
+
+	fn get_content_at<'a>(&'a self, _label: Label, name: &str) -> Option<&'a ContentType<'c>> {
+		self.get_content(name)
+	}
+
+	fn get_condition_at(&self, _label: Label, name: &str) -> bool {
+		self.get_condition(name)
+	}
+
+	fn is_content_defined_at(&self, _label: Label, name: &str) -> bool {
+		self.is_content_defined(name)
+	}
+
+	///Add the labels that have content, conditions and generators to `labels`.
+	///
+	///This is used when checking templates and does nothing by default.
+	fn collect_labels(&self, _labels: &mut DefinedLabels) {}
+
+	///The locale for numbers, currencies and percentages in format specs.
+	///
+	///There is no locale by default, which means that `en-US` is used.
+	fn get_locale(&self) -> Option<&Locale> {
+		None
+	}
+
+	///The time that the `relative` filter compares dates with.
+	///
+	///There is no time by default, which means that the current time is used.
+	fn get_now(&self) -> Option<DateTime> {
+		None
+	}
+
+	///The message catalogs for translation tokens.
+	///
+	///There are no catalogs by default, which means that the message ids are written.
+	fn get_translations(&self) -> Option<&Translations> {
+		None
+	}
+
+	///The languages for translation tokens, in the order they are tried in.
+	fn get_languages(&self) -> &[String] {
+		&[]
+	}
+
+	///The fallback for `label`, which is consulted if it has no content or
+	///condition. There is no fallback by default.
+	fn get_resolver(&self, _label: &str) -> Option<&dyn Resolver> {
+		None
+	}
+}
+
+///A trait for overridable templates.
+pub trait InnerTemplate<'c>: Lookup<'c> {
+	fn get_tokens(&self) -> &[Spanned<Token>];
+	fn render(&self, top_template: &dyn Lookup, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+
+///Look up a path, like `user.address.city`, in the maps and lists of one
+///layer of content. The longest prefix that `get` finds is used, so `user`
+///is only used if there's nothing for `user.address`.
+///
+///The result is `None` if no prefix is found, and `Some(None)` if the
+///prefix is found but the rest of the path isn't. `get` should return
+///`Some(None)` for labels that are explicitly unset.
+fn find_path<'a, 'c: 'a, F>(label: &str, get: F) -> Option<Option<&'a ContentType<'c>>> where
+	F: Fn(&str) -> Option<Option<&'a ContentType<'c>>>
+{
+	let mut end = label.len();
+
+	while let Some(dot) = label[..end].rfind('.') {
+		if let Some(content) = get(&label[..dot]) {
+			return Some(content.and_then(|content| content.get_path(&label[dot + 1..])));
+		}
+
+		end = dot;
+	}
+
+	None
+}
+
+///Conditions can be set with `true` values in maps.
+fn is_true(content: Option<Option<&ContentType>>) -> bool {
+	match content {
+		Some(Some(&ContentType::Bool(value))) => value,
+		_ => false
+	}
+}
+
+
+///A trait for content generators.
+///
+///The arguments of the generator token are passed together with where they
+///are in the template source. They dereference to `String`, and a list of
+///them can be joined like a list of strings.
+pub trait Generator: Send + Sync {
+	fn generate(&self, args: &[Spanned<String>], formatter:  &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl<F: Send + Sync + Fn(&[Spanned<String>], & mut fmt::Formatter) -> fmt::Result> Generator for F {
+	fn generate(&self, args: &[Spanned<String>], formatter:  &mut fmt::Formatter) -> fmt::Result {
+		(*self)(args, formatter)
+	}
+}
+
+
+
+
+///The line of a byte offset in `source`, starting at 1.
+fn line_of(source: &str, offset: usize) -> usize {
+	let offset = offset.min(source.len());
+	source.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+fn format_tokens(template: &dyn Lookup, tokens: &[Spanned<Token>], escaping: &Escaping, f: &mut fmt::Formatter) -> fmt::Result {
+	let _render = lazy::RenderScope::enter();
+	render_tokens(template, tokens, escaping, &mut Resolved::default(), f)
+}
+
+///What the resolver has returned during a rendering, so that each label is
+///resolved at most once, just like when a `Program` is rendered.
+#[derive(Default)]
+struct Resolved<'a> {
+	content: HashMap<&'a str, Option<ContentType<'static>>>,
+	conditions: HashMap<&'a str, bool>,
+	defined: HashMap<&'a str, bool>
+}
+
+fn render_tokens<'a>(template: &dyn Lookup, tokens: &'a [Spanned<Token>], escaping: &Escaping, resolved: &mut Resolved<'a>, f: &mut fmt::Formatter) -> fmt::Result {
+	for token in tokens.iter() {
+		let res = match &token.node {
+			Token::String(s) => f.write_str(s),
+
+			Token::Placeholder(k) => {
+				match template.get_content(k) {
+					Some(value) => escape::write_content(value, escaping, f),
+					None => match resolved.content.entry(k).or_insert_with(|| resolver::content(template, k)) {
+						Some(value) => escape::write_content(value, escaping, f),
+						None => Ok(())
+					}
+				}
+			},
+
+			Token::Filtered(k, filters) => {
+				match template.get_content(k) {
+					Some(value) => filter::write_filtered(value, filters, escaping, template.get_locale(), template.get_now(), f),
+					None => match resolved.content.entry(k).or_insert_with(|| resolver::content(template, k)) {
+						Some(value) => filter::write_filtered(value, filters, escaping, template.get_locale(), template.get_now(), f),
+						None => Ok(())
+					}
+				}
+			},
+
+			&Token::Conditional(ref k, expected, ref tokens) => {
+				let value = template.get_condition(k) ||
+					*resolved.conditions.entry(k).or_insert_with(|| resolver::condition(template, k));
+
+				if value == expected {
+					render_tokens(template, tokens, escaping, resolved, f)
+				} else {
+					Ok(())
+				}
+			},
+
+			&Token::ContentConditional(ref k, expected, ref tokens) => {
+				let value = template.is_content_defined(k) ||
+					*resolved.defined.entry(k).or_insert_with(|| resolver::is_defined(template, k));
+
+				if value == expected {
+					render_tokens(template, tokens, escaping, resolved, f)
+				} else {
+					Ok(())
+				}
+			},
+
+			&Token::Include(_) => Ok(()),
+
+			Token::Translated(id, args) => catalog::write_translation(template, id, args, escaping, f),
+
+			Token::Generated(k, vars) => {
+				match template.get_generator(k) {
+					Some(gen) => gen.generate(vars, f),
+					None => Ok(())
+				}
+			}
+		};
+
+		res?
+	}
+
+	Ok(())
+}
+
+
+
+
+#[cfg(test)]
+mod test {
+	use super::parser::parse;
+	use super::number;
+	use super::{Template, CompiledTemplate, Context, ContentType, Safe, Escaping, Escaper, Filter, Token, Span, Spanned, TemplateSet, Loader, LoadError, TemplateContext, Visitor, VisitorMut, Fold, Instruction, Usage, Block, Suggestion, SignificantDigits, ExponentFormat, Locale, DateTime, Argument, ArgumentValue, Catalog, Translations, PluralCategory, Extractor, Lazy, Resolver, Environment, Lookup};
+	use std::fmt;
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::thread;
+	use std::collections::HashMap;
+	use std::borrow::ToOwned;
+
+	macro_rules! test_insert {
+		($($v:expr),+) => {
+			#[test]
+			fn test_insert() {
+				let mut template: Template = monitored_from_str("[[:v]]");
+				$(
+					template.insert("v".to_owned(), $v);
+					assert_eq!(template.to_string(), $v.to_string());
+				)+
+			}
+		}
+	}
+
+	static PETER: &str = "Peter";
+	static NICE: &str = "nice";
+
+	fn monitored_from_str(s: &str) -> Template<'_> {
+		match Template::from_chars(s.chars()) {
+			Ok(template) => template,
+			Err(e) => panic!("{}", e)
+		}
+	}
+
+	fn echo(parts: &[Spanned<String>], f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&parts.join(":"), f)
+	}
+
+	fn echo2(parts: &[Spanned<String>], f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&parts.join("_"), f)
+	}
+
+	#[test]
+	fn basic_tokens() {
+		let template: Template = "Hello, [[:name]]! This is a [[:something]] template.".parse().unwrap();
+		assert_eq!(template.get_tokens()[0], Token::String("Hello, ".to_owned()));
+		assert_eq!(template.get_tokens()[1], Token::Placeholder("name".to_owned()));
+		assert_eq!(template.get_tokens()[2], Token::String("! This is a ".to_owned()));
+		assert_eq!(template.get_tokens()[3], Token::Placeholder("something".to_owned()));
+		assert_eq!(template.get_tokens()[4], Token::String(" template.".to_owned()));
+	}
+
+	#[test]
+	#[should_panic]
+	fn strange_tokens() {
+		let _: Template = "Hello, [[[:name]]]! This is a [[[[:something]] template.".parse().unwrap();
+	}
+
+	#[test]
+	fn escaped_tokens() {
+		let template = monitored_from_str("Hello, [[:name]]! Write placeholders like \\[[:this]] and escape them like \\\\\\[[:this]]");
+		assert_eq!(template.get_tokens()[0], Token::String("Hello, ".to_owned()));
+		assert_eq!(template.get_tokens()[1], Token::Placeholder("name".to_owned()));
+		assert_eq!(template.get_tokens()[2], Token::String("! Write placeholders like [[:this]] and escape them like \\[[:this]]".to_owned()));
 	}
 
 	#[test]
@@ -614,7 +1735,7 @@ mod test {
 		template.insert_generator("say hello".to_owned(), echo);
 		assert_eq!(template.to_string(), "hello:Peter:how are:you?".to_owned());
 
-		template.insert_generator("say hello".to_owned(), |parts: &[String], f: &mut fmt::Formatter| fmt::Display::fmt(&parts.connect(":"), f));
+		template.insert_generator("say hello".to_owned(), |parts: &[Spanned<String>], f: &mut fmt::Formatter| fmt::Display::fmt(&parts.join(":"), f));
 		assert_eq!(template.to_string(), "hello:Peter:how are:you?".to_owned());
 	}
 
@@ -627,6 +1748,1156 @@ mod test {
 		assert_eq!(template.to_string(), "1.2, 1.2000, 1.2".to_owned())
 	}
 
+	#[test]
+	fn float_formatting() {
+		use super::SignificantDigits::{DigAll, DigMax, DigExact};
+		use super::ExponentFormat::{ExpNone, ExpDec, ExpBin};
+
+		assert_eq!(number::format_float(1.26, DigExact(1), ExpNone), "1.3");
+		assert_eq!(number::format_float(9.96, DigExact(1), ExpNone), "10.0");
+		assert_eq!(number::format_float(-0.004, DigExact(2), ExpNone), "0.00");
+		assert_eq!(number::format_float(-0.004, DigMax(2), ExpNone), "0");
+		assert_eq!(number::format_float(0.999, DigMax(2), ExpNone), "1");
+		assert_eq!(number::format_float(1.2345, DigMax(3), ExpNone), "1.234");
+		assert_eq!(number::format_float(1.5, DigMax(3), ExpNone), "1.5");
+		assert_eq!(number::format_float(1200.0, DigAll, ExpNone), "1200");
+		assert_eq!(number::format_float(0.1 + 0.2, DigAll, ExpNone), "0.30000000000000004");
+
+		assert_eq!(number::format_float(1200.0, DigAll, ExpDec), "1.2e3");
+		assert_eq!(number::format_float(1234.5, DigExact(2), ExpDec), "1.23e3");
+		assert_eq!(number::format_float(0.00125, DigMax(4), ExpDec), "1.25e-3");
+		assert_eq!(number::format_float(1200.0, DigAll, ExpBin), "1.171875p10");
+		assert_eq!(number::format_float(-0.75, DigExact(2), ExpBin), "-1.50p-1");
+
+		assert_eq!(number::format_float(-0.0, DigAll, ExpNone), "0");
+		assert_eq!(number::format_float(-0.0, DigExact(2), ExpNone), "0.00");
+		assert_eq!(number::format_float(-0.0, DigAll, ExpDec), "0e0");
+		assert_eq!(number::format_float(-0.0, DigAll, ExpBin), "0p0");
+
+		for &(digits, exponent) in &[(DigAll, ExpNone), (DigExact(3), ExpDec), (DigMax(2), ExpBin)] {
+			assert_eq!(number::format_float(f64::NAN, digits, exponent), "NaN");
+			assert_eq!(number::format_float(f64::INFINITY, digits, exponent), "inf");
+			assert_eq!(number::format_float(f64::NEG_INFINITY, digits, exponent), "-inf");
+		}
+
+		let mut template = monitored_from_str("[[:nan]], [[:inf]], [[:zero]]");
+		template.insert("nan".to_owned(), f64::NAN);
+		template.insert("inf".to_owned(), f64::NEG_INFINITY);
+		template.insert_formatted_float("zero".to_owned(), -0.0, DigExact(1), ExpNone);
+		assert_eq!(template.to_string(), "NaN, -inf, 0.0");
+	}
+
+	#[test]
+	fn compiled_template_with_context() {
+		let compiled: CompiledTemplate = "Hello, [[:name]]![[?condition]] The condition is true.[[/condition]]".parse().unwrap();
+		let mut context = Context::new();
+		context.insert("name".to_owned(), PETER);
+		assert_eq!(compiled.render(&context).to_string(), "Hello, Peter!".to_owned());
+		context.set("condition".to_owned(), true);
+		assert_eq!(compiled.render(&context).to_string(), "Hello, Peter! The condition is true.".to_owned());
+	}
+
+	#[test]
+	fn shared_compiled_template() {
+		let compiled: Arc<CompiledTemplate> = Arc::new("Hello, [[:name]]!".parse().unwrap());
+
+		let threads: Vec<_> = vec!["Peter", "Olivia"].into_iter().map(|name| {
+			let compiled = compiled.clone();
+			thread::spawn(move || {
+				let mut context = Context::new();
+				context.insert("name".to_owned(), name);
+				compiled.render(&context).to_string()
+			})
+		}).collect();
+
+		let results: Vec<String> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+		assert_eq!(results, vec!["Hello, Peter!".to_owned(), "Hello, Olivia!".to_owned()]);
+
+		let mut template = Template::from_compiled(compiled.clone());
+		template.insert("name".to_owned(), PETER);
+		assert_eq!(template.to_string(), "Hello, Peter!".to_owned());
+	}
+
+	#[test]
+	fn interned_labels() {
+		let mut compiled: CompiledTemplate = "Hello, [[:name]]![[?condition]] [[:name]] is here.[[/]]".parse().unwrap();
+		compiled.compile();
+		let name = compiled.label("name").unwrap();
+		let condition = compiled.label("condition").unwrap();
+		assert!(compiled.label("missing").is_none());
+
+		let mut context = Context::for_template(&compiled);
+		context.insert_at(name, PETER);
+		assert_eq!(context.get_at(name).map(|v| v.to_string()), Some("Peter".to_owned()));
+		assert_eq!(compiled.render(&context).to_string(), "Hello, Peter!".to_owned());
+
+		context.set_at(condition, true);
+		assert_eq!(compiled.render(&context).to_string(), "Hello, Peter! Peter is here.".to_owned());
+
+		context.insert("name".to_owned(), "Olivia");
+		context.set("condition".to_owned(), false);
+		assert_eq!(context.get_at(name).map(|v| v.to_string()), Some("Olivia".to_owned()));
+		assert_eq!(compiled.render(&context).to_string(), "Hello, Olivia!".to_owned());
+	}
+
+	#[test]
+	fn interned_conditions() {
+		let mut compiled: CompiledTemplate = "[[?condition]]true[[/]][[?!condition]]false[[/]]".parse().unwrap();
+		compiled.compile();
+		let condition = compiled.label("condition").unwrap();
+		let mut context = Context::for_template(&compiled);
+
+		context.set("condition".to_owned(), true);
+		context.set_at(condition, false);
+		assert!(!context.get_condition("condition"));
+		assert_eq!(compiled.render(&context).to_string(), "false".to_owned());
+
+		context.set_at(condition, true);
+		context.set("condition".to_owned(), false);
+		assert!(!context.get_condition("condition"));
+		assert_eq!(compiled.render(&context).to_string(), "false".to_owned());
+
+		context.set("condition".to_owned(), true);
+		assert!(context.get_condition("condition"));
+		assert_eq!(compiled.render(&context).to_string(), "true".to_owned());
+	}
+
+	#[test]
+	#[should_panic]
+	fn foreign_label() {
+		let first: CompiledTemplate = "[[:name]]".parse().unwrap();
+		let second: CompiledTemplate = "[[:name]]".parse().unwrap();
+		let mut context = Context::for_template(&second);
+		context.insert_at(first.label("name").unwrap(), PETER);
+	}
+
+	#[test]
+	fn introspection() {
+		let template = monitored_from_str("[[:a]][[?c]][[:b]][[?!:a]][[:a]][[+g x]][[/]][[/]][[+g y]]");
+
+		let labels = |usages: Vec<Usage>| usages.into_iter().map(|u| u.label).collect::<Vec<_>>();
+		assert_eq!(labels(template.placeholders()), vec!["a".to_owned(), "b".to_owned()]);
+		assert_eq!(labels(template.conditions()), vec!["c".to_owned()]);
+		assert_eq!(labels(template.content_conditions()), vec!["a".to_owned()]);
+		assert_eq!(labels(template.generators()), vec!["g".to_owned()]);
+
+		let a = &template.placeholders()[0];
+		assert_eq!(a.occurrences.len(), 2);
+		assert_eq!(a.occurrences[0].path, vec![0]);
+		assert_eq!(a.occurrences[0].depth(), 0);
+		assert_eq!(a.occurrences[1].path, vec![1, 1, 0]);
+		assert_eq!(a.occurrences[1].blocks, vec![
+			Block { label: "c".to_owned(), expected: true, content_condition: false },
+			Block { label: "a".to_owned(), expected: false, content_condition: true }
+		]);
+
+		let g = &template.generators()[0];
+		assert_eq!(g.occurrences.iter().map(|o| o.depth()).collect::<Vec<_>>(), vec![2, 0]);
+	}
+
+	#[test]
+	fn check_template() {
+		let mut template = monitored_from_str("Hello, [[:name]]![[?:title]] [[:title]][[/]][[?condition]] [[+gen]][[/]][[:count]]");
+		template.insert("nmae".to_owned(), PETER);
+		template.insert("unrelated".to_owned(), NICE);
+		template.set("conditon".to_owned(), true);
+
+		let report = template.check();
+		assert!(!report.is_ok());
+		assert_eq!(report.missing_content, vec!["name".to_owned(), "count".to_owned()]);
+		assert_eq!(report.unset_conditions, vec!["condition".to_owned()]);
+		assert_eq!(report.missing_generators, vec!["gen".to_owned()]);
+		assert_eq!(report.unused_content, vec!["nmae".to_owned(), "unrelated".to_owned()]);
+		assert_eq!(report.unused_conditions, vec!["conditon".to_owned()]);
+		assert_eq!(report.suggestions, vec![
+			Suggestion { missing: "name".to_owned(), unused: "nmae".to_owned(), distance: 1 },
+			Suggestion { missing: "condition".to_owned(), unused: "conditon".to_owned(), distance: 1 }
+		]);
+	}
+
+	#[test]
+	fn check_shell() {
+		let mut template = monitored_from_str("Hello, [[:name]]! [[+gen]]");
+		template.insert("name".to_owned(), PETER);
+		template.insert_generator("gen".to_owned(), echo);
+
+		let mut shell = template.wrap();
+		shell.unset("name".to_owned());
+		shell.insert("other".to_owned(), NICE);
+
+		let report = shell.check();
+		assert_eq!(report.missing_content, vec!["name".to_owned()]);
+		assert_eq!(report.unused_content, vec!["other".to_owned()]);
+		assert!(report.missing_generators.is_empty());
+		assert!(template.check().is_ok());
+	}
+
+	#[test]
+	fn generator_arguments() {
+		let template = monitored_from_str("[[+\"a b\"]]x[[+g \"x\"]] y[[+g x ]]z[[+g \"\"]]");
+		assert_eq!(template.get_tokens(), &[
+			Token::Generated("a b".to_owned(), vec![]),
+			Token::String("x".to_owned()),
+			Token::Generated("g".to_owned(), vec!["x".to_owned().into()]),
+			Token::String(" y".to_owned()),
+			Token::Generated("g".to_owned(), vec!["x".to_owned().into()]),
+			Token::String("z".to_owned()),
+			Token::Generated("g".to_owned(), vec!["".to_owned().into()])
+		][..]);
+	}
+
+	#[test]
+	fn to_source() {
+		let template = monitored_from_str("Hello, [[:name]]![[?!:name]] \\[[[?!cond]]x[[/cond]][[/]] [[+\"say hello\" a \"b c\"]]\\[[:this]]");
+		assert_eq!(template.to_source(), "Hello, [[:name]]![[?!:name]] \\[[[?!cond]]x[[/]][[/]] [[+\"say hello\" a \"b c\"]]\\[[:this]]".to_owned());
+		assert_eq!(Token::Placeholder("a]]b".to_owned()).to_string(), "[[:a\\]\\]b]]".to_owned());
+	}
+
+	#[test]
+	fn source_round_trip() {
+		let mut rng = XorShift(0x2545F4914F6CDD1D);
+
+		for _ in 0..2000 {
+			let tokens = random_tokens(&mut rng, 0);
+			let source = CompiledTemplate::from_tokens(tokens.clone()).to_source();
+			let parsed = match parse(source.chars().map(Ok)) {
+				Ok(parsed) => parsed,
+				Err(e) => panic!("failed to parse {:?}: {}", source, e)
+			};
+			assert_eq!(parsed, tokens, "source: {:?}", source);
+		}
+	}
+
+	struct XorShift(u64);
+
+	impl XorShift {
+		fn next(&mut self, bound: u64) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0 % bound
+		}
+	}
+
+	fn random_text(rng: &mut XorShift, allow_empty: bool) -> String {
+		let alphabet = ['a', 'b', ' ', '[', ']', ':', '?', '!', '+', '/', '"', '|', '\\', '\t', 'å'];
+		let min = if allow_empty { 0 } else { 1 };
+		let len = min + rng.next(6) as usize;
+		(0..len).map(|_| alphabet[rng.next(alphabet.len() as u64) as usize]).collect()
+	}
+
+	fn random_spec(rng: &mut XorShift) -> String {
+		let specs = ["", ".2", ">8", "e3", ":^+010.3p", "|<4", "[>2", "\\<0"];
+		specs[rng.next(specs.len() as u64) as usize].to_owned()
+	}
+
+	fn random_argument(rng: &mut XorShift) -> Argument {
+		let names = ["n", "count", "first_name"];
+		let name = names[rng.next(names.len() as u64) as usize].to_owned();
+
+		if rng.next(2) == 0 {
+			Argument::new(name, ArgumentValue::Label(random_text(rng, true)))
+		} else {
+			Argument::new(name, ArgumentValue::Text(random_text(rng, true)))
+		}
+	}
+
+	fn random_tokens(rng: &mut XorShift, depth: usize) -> Vec<Spanned<Token>> {
+		let mut tokens = Vec::new();
+		let mut last_was_string = false;
+
+		for _ in 0..rng.next(5) {
+			let token = match rng.next(if depth < 3 { 5 } else { 3 }) {
+				0 if !last_was_string => Token::String(random_text(rng, false)),
+				1 if rng.next(4) == 0 => Token::Filtered(random_text(rng, true), vec![Filter::new("escape".to_owned(), vec!["url".to_owned()])]),
+				1 if rng.next(4) == 0 => Token::Filtered(random_text(rng, true), vec![Filter::new("format".to_owned(), vec![random_spec(rng)])]),
+				0 | 1 => Token::Placeholder(random_text(rng, true)),
+				2 if rng.next(4) == 0 => Token::Translated(random_text(rng, false), (0..rng.next(3)).map(|_| random_argument(rng)).collect()),
+				2 => Token::Generated(random_text(rng, true), (0..rng.next(3)).map(|_| random_text(rng, true).into()).collect()),
+				3 if rng.next(4) == 0 => Token::Include(random_text(rng, true)),
+				3 => Token::Conditional(random_text(rng, true), rng.next(2) == 0, random_tokens(rng, depth + 1)),
+				_ => Token::ContentConditional(random_text(rng, true), rng.next(2) == 0, random_tokens(rng, depth + 1))
+			};
+
+			last_was_string = matches!(token, Token::String(_));
+			tokens.push(token.into());
+		}
+
+		tokens
+	}
+
+	#[test]
+	fn visitor() {
+		struct Count(usize);
+
+		impl Visitor for Count {
+			fn visit_placeholder(&mut self, _label: &str) {
+				self.0 += 1;
+			}
+		}
+
+		let template = monitored_from_str("[[:a]][[?c]][[:b]][[?!:a]][[:a]][[/]][[/]]");
+		let mut count = Count(0);
+		count.visit_tokens(template.get_tokens());
+		assert_eq!(count.0, 3);
+	}
+
+	#[test]
+	fn visitor_mut_rename() {
+		struct Rename;
+
+		impl VisitorMut for Rename {
+			fn visit_placeholder_mut(&mut self, label: &mut String) {
+				if label == "name" {
+					*label = "first_name".to_owned();
+				}
+			}
+
+			fn visit_content_conditional_mut(&mut self, label: &mut String, _expected: &mut bool, tokens: &mut Vec<Spanned<Token>>) {
+				self.visit_placeholder_mut(label);
+				self.visit_tokens_mut(tokens);
+			}
+		}
+
+		let mut template = monitored_from_str("Hello[[?:name]], [[:name]][[/]]!");
+		template.visit_mut(&mut Rename);
+		template.insert("first_name".to_owned(), PETER);
+		assert_eq!(template.to_string(), "Hello, Peter!".to_owned());
+		assert!(template.get_compiled().label("name").is_none());
+	}
+
+	#[test]
+	fn fold_inline_and_strip() {
+		struct Specialize;
+
+		impl Fold for Specialize {
+			fn fold_placeholder(&mut self, label: String, span: Span) -> Vec<Spanned<Token>> {
+				if label == "name" {
+					vec![Spanned::new(Token::String(PETER.to_owned()), span)]
+				} else {
+					vec![Spanned::new(Token::Placeholder(label), span)]
+				}
+			}
+
+			fn fold_conditional(&mut self, label: String, expected: bool, tokens: Vec<Spanned<Token>>, span: Span) -> Vec<Spanned<Token>> {
+				if label == "debug" {
+					vec![]
+				} else {
+					vec![Spanned::new(Token::Conditional(label, expected, self.fold_tokens(tokens)), span)]
+				}
+			}
+		}
+
+		let mut template = monitored_from_str("Hello, [[:name]]![[?debug]] [[:details]][[/]] This is a [[:something]] template.");
+		template.transform(&mut Specialize);
+		assert_eq!(template.get_tokens(), &[
+			Token::String("Hello, Peter! This is a ".to_owned()),
+			Token::Placeholder("something".to_owned()),
+			Token::String(" template.".to_owned())
+		][..]);
+		assert_eq!(template.get_tokens()[0].span, Span::new(0, 56));
+		assert_eq!(template.get_tokens()[1].span, Span::new(56, 70));
+	}
+
+	#[test]
+	fn spans() {
+		let template = monitored_from_str("Hello, [[:name]]!");
+		let spans: Vec<Span> = template.get_tokens().iter().map(|t| t.span).collect();
+		assert_eq!(spans, vec![Span::new(0, 7), Span::new(7, 16), Span::new(16, 17)]);
+
+		let source = "å\\[[[?:x]]a[[+g \"b c\" d]][[/]]";
+		let template = monitored_from_str(source);
+		let tokens = template.get_tokens();
+		assert_eq!(&source[tokens[0].span.start..tokens[0].span.end], "å\\[");
+		assert_eq!(&source[tokens[1].span.start..tokens[1].span.end], "[[?:x]]a[[+g \"b c\" d]][[/]]");
+
+		match tokens[1].node {
+			Token::ContentConditional(_, _, ref content) => {
+				assert_eq!(&source[content[0].span.start..content[0].span.end], "a");
+				assert_eq!(&source[content[1].span.start..content[1].span.end], "[[+g \"b c\" d]]");
+
+				match content[1].node {
+					Token::Generated(_, ref args) => {
+						assert_eq!(args[0], "b c".to_owned());
+						assert_eq!(&source[args[0].span.start..args[0].span.end], "\"b c\"");
+						assert_eq!(&source[args[1].span.start..args[1].span.end], "d");
+					},
+					ref t => panic!("expected a generator, got {:?}", t)
+				}
+			},
+			ref t => panic!("expected a content conditional, got {:?}", t)
+		}
+	}
+
+	#[test]
+	fn specialize() {
+		let mut context = Context::new();
+		context.insert("name".to_owned(), PETER);
+		context.insert("count".to_owned(), 3);
+		context.set("beta".to_owned(), true);
+		let other = monitored_from_str("nested");
+		context.insert("nested".to_owned(), other);
+
+		let template = monitored_from_str("Hello, [[:name]]![[?beta]] You have [[:count]] new[[?:count]] messages[[/]].[[/]][[?!beta]] Old.[[/]][[?debug]] [[:details]][[/]] [[:nested]]");
+		let mut specialized = template.specialize(&context);
+		assert_eq!(specialized.get_tokens(), &[
+			Token::String("Hello, Peter! You have 3 new messages.".to_owned()),
+			Token::Conditional("debug".to_owned(), true, vec![
+				Token::String(" ".to_owned()).into(),
+				Token::Placeholder("details".to_owned()).into()
+			]),
+			Token::String(" ".to_owned()),
+			Token::Placeholder("nested".to_owned())
+		][..]);
+
+		specialized.set("debug".to_owned(), true);
+		specialized.insert("details".to_owned(), "details");
+		specialized.insert("nested".to_owned(), "nested");
+		assert_eq!(specialized.to_string(), "Hello, Peter! You have 3 new messages. details nested".to_owned());
+	}
+
+	#[test]
+	fn includes() {
+		let mut set = TemplateSet::new();
+		set.insert("header".to_owned(), "<h1>[[:title]]</h1>".parse().unwrap()).unwrap();
+		let errors = set.insert("page".to_owned(), "[[>header]]Hello, [[:name]]![[>footer]]".parse().unwrap()).unwrap_err();
+		assert_eq!(errors[0].message, "the included template 'footer' at byte 28 doesn't exist".to_owned());
+		assert!(set.insert("footer".to_owned(), "[[?signed]] -- [[>signature]][[/]]".parse().unwrap()).is_err());
+		set.insert("signature".to_owned(), "[[:name]]".parse().unwrap()).unwrap();
+
+		let mut template = set.template("page").unwrap();
+		template.insert("title".to_owned(), "Welcome");
+		template.insert("name".to_owned(), PETER);
+		template.set("signed".to_owned(), true);
+		assert_eq!(template.to_string(), "<h1>Welcome</h1>Hello, Peter! -- Peter".to_owned());
+		assert_eq!(set.get_source("page").unwrap().to_source(), "[[>header]]Hello, [[:name]]![[>footer]]".to_owned());
+
+		let errors = set.insert("signature".to_owned(), "[[>page]]".parse().unwrap()).unwrap_err();
+		assert_eq!(errors.len(), 3);
+		assert_eq!(errors[0], LoadError {
+			name: "footer".to_owned(),
+			message: "include cycle: footer -> signature -> page -> footer".to_owned()
+		});
+	}
+
+	#[test]
+	fn loader() {
+		use std::fs::{self, File};
+		use std::io::Write;
+
+		let root = ::std::env::temp_dir().join(format!("fragments-loader-{}", ::std::process::id()));
+		let _ = fs::remove_dir_all(&root);
+		fs::create_dir_all(root.join("partials")).unwrap();
+
+		let write = |path: &str, content: &str| {
+			let mut file = File::create(root.join(path)).unwrap();
+			file.write_all(content.as_bytes()).unwrap();
+		};
+
+		write("page.tmpl", "[[>partials/header]]Hello, [[:name]]!");
+		write("partials/header.tmpl", "<h1>[[:title]]</h1>");
+		write("notes.txt", "[[invalid]]");
+
+		let loader = Loader::new(&root, "tmpl");
+		let set = loader.load().unwrap();
+		assert_eq!(set.names(), vec!["page", "partials/header"]);
+
+		let mut template = set.template("page").unwrap();
+		template.insert("title".to_owned(), "Welcome");
+		template.insert("name".to_owned(), PETER);
+		assert_eq!(template.to_string(), "<h1>Welcome</h1>Hello, Peter!".to_owned());
+
+		write("broken.tmpl", "[[invalid]]");
+		write("partials/footer.tmpl", "[[>missing]]");
+		let errors = loader.load().unwrap_err();
+		assert_eq!(errors.iter().map(|e| &*e.name).collect::<Vec<_>>(), vec!["broken", "partials/footer"]);
+
+		#[cfg(unix)]
+		{
+			fs::remove_file(root.join("broken.tmpl")).unwrap();
+			fs::remove_file(root.join("partials/footer.tmpl")).unwrap();
+			::std::os::unix::fs::symlink(&root, root.join("partials/root")).unwrap();
+			assert_eq!(loader.load().unwrap().names(), vec!["page", "partials/header"]);
+		}
+
+		fs::remove_dir_all(&root).unwrap();
+	}
+
+	#[test]
+	fn watcher() {
+		use std::fs::{self, File};
+		use std::io::Write;
+		use std::time::Duration;
+
+		for &polling in [false, true].iter() {
+			let root = ::std::env::temp_dir().join(format!("fragments-watcher-{}-{}", ::std::process::id(), polling));
+			let _ = fs::remove_dir_all(&root);
+			fs::create_dir_all(&root).unwrap();
+
+			let write = |path: &str, content: &str| {
+				let mut file = File::create(root.join(path)).unwrap();
+				file.write_all(content.as_bytes()).unwrap();
+			};
+
+			write("page.tmpl", "[[>header]]Hello, [[:name]]!");
+			write("header.tmpl", "<h1>Welcome</h1>");
+
+			let loader = Loader::new(&root, "tmpl");
+			let watcher = if polling {
+				loader.poll(Duration::from_millis(0))
+			} else {
+				loader.watch()
+			};
+			assert!(watcher.errors().is_empty());
+
+			let render = |name: &str| {
+				let mut template = watcher.template(name).unwrap();
+				template.insert("name".to_owned(), PETER);
+				template.to_string()
+			};
+			assert_eq!(render("page"), "<h1>Welcome</h1>Hello, Peter!".to_owned());
+
+			write("header.tmpl", "<h1>Welcome back</h1>");
+			assert_eq!(render("page"), "<h1>Welcome back</h1>Hello, Peter!".to_owned());
+
+			write("header.tmpl", "<h1>[[Welcome]]</h1>");
+			assert_eq!(render("page"), "<h1>Welcome back</h1>Hello, Peter!".to_owned());
+			assert_eq!(watcher.errors().len(), 1);
+			assert_eq!(watcher.errors()[0].name, "header".to_owned());
+
+			fs::remove_file(root.join("header.tmpl")).unwrap();
+			assert_eq!(render("page"), "Hello, Peter!".to_owned());
+			assert_eq!(watcher.errors().len(), 1);
+			assert_eq!(watcher.errors()[0].name, "page".to_owned());
+
+			write("header.tmpl", "<h1>Hi</h1>");
+			assert_eq!(render("page"), "<h1>Hi</h1>Hello, Peter!".to_owned());
+			assert!(watcher.errors().is_empty());
+
+			if !watcher.is_polling() {
+				//inotify reports changes that keep the size and the modification time
+				let modified = fs::metadata(root.join("header.tmpl")).unwrap().modified().unwrap();
+				write("header.tmpl", "<h1>Ho</h1>");
+				File::options().write(true).open(root.join("header.tmpl")).unwrap().set_modified(modified).unwrap();
+				assert_eq!(render("page"), "<h1>Ho</h1>Hello, Peter!".to_owned());
+			}
+
+			fs::remove_dir_all(&root).unwrap();
+		}
+	}
+
+	#[test]
+	fn template_macro() {
+		fn spans(tokens: &[Spanned<Token>]) -> Vec<Span> {
+			tokens.iter().map(|t| t.span).collect()
+		}
+
+		let source = "Hello, [[:name]]![[?:name]] [[+g a \"b c\"]][[/]]";
+		let compiled = ::template!("Hello, [[:name]]![[?:name]] [[+g a \"b c\"]][[/]]");
+		let parsed = parse(source.chars().map(Ok)).unwrap();
+		assert_eq!(compiled.get_tokens(), &parsed[..]);
+		assert_eq!(spans(compiled.get_tokens()), spans(&parsed));
+
+		let mut template = Template::from_compiled(compiled);
+		template.insert("name".to_owned(), PETER);
+		assert_eq!(template.to_string(), "Hello, Peter! ".to_owned());
+	}
+
+	#[test]
+	fn template_context() {
+		#[derive(TemplateContext)]
+		#[template(source = "[[:name]] lives in [[:address.city]][[?:address.street]], [[:address.street]][[/]].[[?admin]] (admin)[[/]] [[:visits]]")]
+		struct User {
+			name: String,
+			address: Address,
+			admin: bool,
+			#[template(rename = "visits")]
+			visit_count: u32,
+			#[template(skip)]
+			#[allow(dead_code)]
+			password: String
+		}
+
+		#[derive(TemplateContext)]
+		struct Address {
+			city: &'static str,
+			street: Option<String>
+		}
+
+		let mut user = User {
+			name: PETER.to_owned(),
+			address: Address {
+				city: "Stockholm",
+				street: None
+			},
+			admin: true,
+			visit_count: 3,
+			password: "secret".to_owned()
+		};
+
+		let compiled = {
+			let mut template = monitored_from_str("[[:name]] lives in [[:address.city]][[?:address.street]], [[:address.street]][[/]].[[?admin]] (admin)[[/]] [[:visits]][[:password]]");
+			template.fill(&user);
+			assert_eq!(template.to_string(), "Peter lives in Stockholm. (admin) 3".to_owned());
+			template.get_compiled().clone()
+		};
+
+		user.address.street = Some("Drottninggatan".to_owned());
+		let mut context = Context::for_template(&compiled);
+		context.fill(&user);
+		assert_eq!(compiled.render(&context).to_string(), "Peter lives in Stockholm, Drottninggatan. (admin) 3".to_owned());
+	}
+
+	#[test]
+	fn escaping() {
+		let mut inner = monitored_from_str("<i>[[:text]]</i>");
+		inner.insert("text".to_owned(), "a & b");
+
+		let mut template = monitored_from_str("<p title=\"[[:title]]\">[[:text]] [[:safe]] [[:inner]] [[:list]]</p>");
+		template.set_escaping(Escaping::Html);
+		template.insert("title".to_owned(), "\"quoted\" 'text'");
+		template.insert("text".to_owned(), "<script>");
+		template.insert("safe".to_owned(), Safe("<b>bold</b>"));
+		template.insert("inner".to_owned(), inner);
+		template.insert("list".to_owned(), vec![ContentType::Char('<'), ContentType::Safe(Box::new(ContentType::Char('>')))]);
+
+		let expected = "<p title=\"&quot;quoted&quot; &#39;text&#39;\">&lt;script&gt; <b>bold</b> <i>a & b</i> &lt;></p>";
+		assert_eq!(template.to_string(), expected.to_owned());
+
+		let specialized = template.specialize(&template);
+		assert_eq!(specialized.to_source(), "<p title=\"&quot;quoted&quot; &#39;text&#39;\">&lt;script&gt; <b>bold</b> [[:inner]] &lt;></p>".to_owned());
+
+		template.compile();
+		assert_eq!(template.to_string(), expected.to_owned());
+
+		assert_eq!(Escaping::for_extension("html"), Escaping::Html);
+		assert_eq!(Escaping::for_extension("HTM"), Escaping::Html);
+		assert_eq!(Escaping::for_extension("txt"), Escaping::None);
+	}
+
+	#[test]
+	fn escapers() {
+		struct Upper;
+
+		impl Escaper for Upper {
+			fn escape(&self, s: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+				out.write_str(&s.to_uppercase())
+			}
+		}
+
+		let source = "[[:v|escape html]];[[:v|escape json]];[[:v|escape url]];[[:v|escape csv]];[[:v|escape shell]];[[:v|escape xml]];[[:v|escape none]];[[:v]]";
+		let mut template = monitored_from_str(source);
+		template.set_escaping(Escaping::Custom(Arc::new(Upper)));
+		template.insert("v".to_owned(), "it's <a \"b\">, c&d/\n");
+
+		let expected = ["it&#39;s &lt;a &quot;b&quot;&gt;, c&amp;d/\n",
+			"it's <a \\\"b\\\">, c&d/\\n",
+			"it%27s%20%3Ca%20%22b%22%3E%2C%20c%26d%2F%0A",
+			"\"it's <a \"\"b\"\">, c&d/\n\"",
+			"'it'\\''s <a \"b\">, c&d/\n'",
+			"it&apos;s &lt;a &quot;b&quot;&gt;, c&amp;d/&#10;",
+			"it's <a \"b\">, c&d/\n",
+			"IT'S <A \"B\">, C&D/\n"];
+		assert_eq!(template.to_string(), expected.join(";"));
+
+		template.compile();
+		assert_eq!(template.to_string(), expected.join(";"));
+
+		template.insert("v".to_owned(), "plain-word_1.txt");
+		assert_eq!(template.to_string().split(';').nth(4), Some("plain-word_1.txt"));
+
+		let tokens = parse("[[:a\\|b | escape \"url\"|escape html]]".chars().map(Ok)).unwrap();
+		assert_eq!(tokens, vec![Spanned::from(Token::Filtered("a|b ".to_owned(), vec![
+			Filter::new("escape".to_owned(), vec!["url".to_owned()]),
+			Filter::new("escape".to_owned(), vec!["html".to_owned()])
+		]))]);
+		assert_eq!(CompiledTemplate::from_tokens(tokens).to_source(), "[[:a\\|b |escape url|escape html]]".to_owned());
+
+		assert!(Template::from_chars("[[:a|escape sql]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a|upper]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a|]]".chars()).is_err());
+		assert_eq!("Hello,\n[[:a|upper]]".parse::<CompiledTemplate>().unwrap_err(), "line 2: parse error: unknown filter: 'upper'".to_owned());
+	}
+
+	#[test]
+	fn format_specs() {
+		let source = "[[:price:.2]];[[:id:>8]];[[:id:*^7]];[[:ratio:e3]];[[:ratio:+08.1]];[[:name:.3]];[[:name:-<6]];[[:count:+]];[[:count:.1e]];[[:big:p]];[[:html:>6|escape html]]";
+		let mut template = monitored_from_str(source);
+		template.insert("price".to_owned(), 4.5);
+		template.insert("id".to_owned(), 42u32);
+		template.insert("ratio".to_owned(), -0.012345);
+		template.insert("name".to_owned(), "Peter");
+		template.insert("count".to_owned(), 1250);
+		template.insert_formatted_float("big".to_owned(), 1200.0, SignificantDigits::DigMax(2), ExponentFormat::ExpNone);
+		template.insert("html".to_owned(), "<b>");
+
+		let expected = "4.50;      42;**42***;-1.235e-2;+00000.0;Pet;Peter-;+1250;1.2e3;1.17p10;   &lt;b&gt;";
+		assert_eq!(template.to_string(), expected);
+
+		template.compile();
+		assert_eq!(template.to_string(), expected);
+
+		let tokens = parse("[[:a\\:b::>5|escape url]][[:c:]]".chars().map(Ok)).unwrap();
+		assert_eq!(tokens, vec![
+			Spanned::from(Token::Filtered("a:b".to_owned(), vec![
+				Filter::new("format".to_owned(), vec![":>5".to_owned()]),
+				Filter::new("escape".to_owned(), vec!["url".to_owned()])
+			])),
+			Spanned::from(Token::Filtered("c".to_owned(), vec![Filter::new("format".to_owned(), vec!["".to_owned()])]))
+		]);
+		assert_eq!(CompiledTemplate::from_tokens(tokens).to_source(), "[[:a\\:b:\\:>5|escape url]][[:c:]]".to_owned());
+
+		assert!(Template::from_chars("[[:a:.]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a:x]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a:.2e3]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a|format]]".chars()).is_err());
+
+		let error = parse("[[:a:>99999999999999999999]]".chars().map(Ok)).unwrap_err();
+		assert_eq!(error.node, "parse error: invalid format spec '>99999999999999999999': the number is too large".to_owned());
+		assert_eq!(error.span, Span::new(6, 26));
+		let error = parse("[[:a|format \".99999999999999999999\"]]".chars().map(Ok)).unwrap_err();
+		assert_eq!(error.span, Span::new(14, 34));
+		assert_eq!(parse("[[:a:.2x]]".chars().map(Ok)).unwrap_err().span, Span::new(7, 8));
+	}
+
+	#[test]
+	fn locales() {
+		let source = "[[:total:c]];[[:total:.1n]];[[:count:n]];[[:rate:%]];[[:rate:.1%]];[[:debt:c]];[[:total]]";
+		let mut template = monitored_from_str(source);
+		template.insert("total".to_owned(), 1234.5);
+		template.insert("count".to_owned(), -1234567);
+		template.insert("rate".to_owned(), 0.125);
+		template.insert("debt".to_owned(), -99);
+
+		assert_eq!(template.to_string(), "$1,234.50;1,234.5;-1,234,567;12%;12.5%;-$99.00;1234.5");
+
+		template.set_locale(Locale::from_tag("de_DE").unwrap());
+		let expected = "1.234,50 €;1.234,5;-1.234.567;12 %;12,5 %;-99,00 €;1234.5";
+		assert_eq!(template.to_string(), expected);
+
+		template.compile();
+		assert_eq!(template.to_string(), expected);
+		assert_eq!(template.specialize(&template).to_string(), expected);
+
+		let mut context = Context::new();
+		context.insert("total".to_owned(), 1234.5);
+		context.set_locale(Locale::from_tag("fr-CA").unwrap().with_currency("$"));
+		let compiled: CompiledTemplate = "[[:total:c]]".parse().unwrap();
+		assert_eq!(compiled.render(&context).to_string(), "1 234,50 $");
+
+		{
+			let mut shell = template.wrap();
+			shell.set_locale(Locale::from_tag("en-GB").unwrap());
+			shell.insert("debt".to_owned(), 1000000u64);
+			assert_eq!(shell.to_string(), "£1,234.50;1,234.5;-1,234,567;12%;12.5%;£1,000,000.00;1234.5");
+		}
+
+		assert_eq!(Locale::default(), Locale::from_tag("en").unwrap());
+		assert!(Locale::from_tag("xx-YY").is_none());
+	}
+
+	#[test]
+	fn dates() {
+		let created = DateTime::new(2015, 3, 14, 9, 26, 53, 3600).unwrap();
+		assert_eq!(created.timestamp(), 1426321613);
+		assert_eq!(created.to_string(), "2015-03-14T09:26:53+01:00");
+		assert_eq!(created.with_offset(0).unwrap().to_string(), "2015-03-14T08:26:53Z");
+		assert_eq!(created.with_offset(-5 * 3600 - 1800).unwrap().format("%F %T %z %Z").to_string(), "2015-03-14 02:56:53 -0530 -05:30");
+		assert_eq!(created.format("%a %A %b %B %e %-d %j %u %w %y %C %I%p %%").to_string(), "Sat Saturday Mar March 14 14 073 6 6 15 20 09AM %");
+		assert_eq!(DateTime::from_timestamp(-1, 0).unwrap().to_string(), "1969-12-31T23:59:59Z");
+		assert_eq!(DateTime::new(2000, 2, 29, 23, 0, 0, 0).unwrap().format("%D %R %-m").to_string(), "02/29/00 23:00 2");
+		assert!(DateTime::new(1900, 2, 29, 0, 0, 0, 0).is_none());
+		assert!(DateTime::new(2015, 13, 1, 0, 0, 0, 0).is_none());
+		assert!(DateTime::new(2015, 3, 14, 0, 0, 0, i32::MIN).is_none());
+		assert!(DateTime::new(i64::MAX, 1, 1, 0, 0, 0, 0).is_none());
+		assert!(DateTime::new(300_000_000_000, 1, 1, 0, 0, 0, 0).is_none());
+		assert!(DateTime::from_timestamp(0, 86400).is_none());
+		assert!(created.with_offset(i32::MIN).is_none());
+		assert_eq!(DateTime::from_timestamp(i64::MAX, 86399).unwrap().format("%H:%M:%S").to_string(), "15:30:07");
+		assert_eq!(DateTime::from_timestamp(i64::MIN, -86399).unwrap().format("%H:%M:%S").to_string(), "08:29:52");
+
+		let now = DateTime::from_timestamp(1426321613, 0).unwrap();
+		let relative = |seconds: i64| DateTime::from_timestamp(now.timestamp() - seconds, 0).unwrap().relative_to(&now);
+		assert_eq!(relative(30), "just now");
+		assert_eq!(relative(60), "1 minute ago");
+		assert_eq!(relative(3 * 86400 + 100), "3 days ago");
+		assert_eq!(relative(-2 * 3600), "in 2 hours");
+		assert_eq!(relative(400 * 86400), "1 year ago");
+
+		let source = "[[:created|date \"%Y-%m-%d\"]];[[:created]];[[:stamp|date \"%H:%M\"]];[[:created:>8|date \"%d %b\"]];[[:old|relative]];[[:soon|relative]]";
+		let mut template = monitored_from_str(source);
+		template.set_now(now);
+		template.insert("created".to_owned(), created);
+		template.insert("stamp".to_owned(), 1426321613);
+		template.insert("old".to_owned(), DateTime::from_timestamp(now.timestamp() - 3 * 86400 - 100, 3600).unwrap());
+		template.insert("soon".to_owned(), std::time::UNIX_EPOCH + std::time::Duration::from_secs(1426321613 + 5 * 86400 + 100));
+
+		let expected = "2015-03-14;2015-03-14T09:26:53+01:00;08:26;  14 Mar;3 days ago;in 5 days";
+		assert_eq!(template.to_string(), expected);
+		assert_eq!(template.specialize(&template).to_source(), "2015-03-14;2015-03-14T09:26:53+01:00;08:26;  14 Mar;[[:old|relative]];[[:soon|relative]]");
+
+		template.compile();
+		assert_eq!(template.to_string(), expected);
+
+		let mut shell = template.wrap();
+		shell.set_now(DateTime::from_timestamp(now.timestamp() + 86400, 0).unwrap());
+		assert_eq!(shell.to_string(), "2015-03-14;2015-03-14T09:26:53+01:00;08:26;  14 Mar;4 days ago;in 4 days");
+
+		let compiled = template.get_compiled().clone();
+		let mut context = Context::for_template(&compiled);
+		context.insert("old".to_owned(), created);
+		context.set_now(DateTime::from_timestamp(now.timestamp() + 2 * 3600, 0).unwrap());
+		assert_eq!(compiled.render(&context).to_string(), ";;;;2 hours ago;");
+
+		context.insert("old".to_owned(), i64::MIN);
+		context.insert("soon".to_owned(), DateTime::from_timestamp(i64::MAX, 0).unwrap());
+		context.set_now(DateTime::from_timestamp(i64::MAX, 0).unwrap());
+		assert_eq!(compiled.render(&context).to_string(), ";;;;292471208677 years ago;just now");
+
+		assert!(Template::from_chars("[[:a|date \"%Q\"]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a|date \"%Y\" x]]".chars()).is_err());
+		assert!(Template::from_chars("[[:a|relative now]]".chars()).is_err());
+	}
+
+	#[test]
+	fn translations() {
+		let english = [
+			"# English",
+			"greeting = Hello, { $name }!",
+			"items = { $count ->",
+			"    [0] No items",
+			"    [one] One item",
+			"   *[other] { $count } items",
+			"}",
+			"-brand = Fragments",
+			"about = About { -brand }"
+		].join("\n");
+		let german = "greeting = Hallo, { $name }!";
+		let russian = [
+			"msgid \"\"",
+			"msgstr \"Plural-Forms: nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);\\n\"",
+			"",
+			"#, fuzzy",
+			"msgid \"greeting\"",
+			"msgstr \"Привет?\"",
+			"",
+			"msgid \"items\"",
+			"msgid_plural \"items\"",
+			"msgstr[0] \"{count} предмет\"",
+			"msgstr[1] \"{count} предмета\"",
+			"msgstr[2] \"{count} \"",
+			"\"предметов\""
+		].join("\n");
+
+		let mut translations = Translations::new();
+		translations.add(Catalog::from_fluent("en", &english).unwrap());
+		translations.add(Catalog::from_fluent("de", german).unwrap());
+		translations.add(Catalog::from_po("ru", &russian).unwrap());
+		let translations = Arc::new(translations);
+		assert_eq!(translations.get("RU").unwrap().ids(), vec!["items"]);
+
+		let source = "[[~greeting name=:name]] [[~items count=:count]] [[~about]] [[~\"Bye, {name}!\" name=\"you\"]]";
+		let mut template = monitored_from_str(source);
+		template.insert("name".to_owned(), "<Anna>");
+		template.insert("count".to_owned(), 21);
+		assert_eq!(template.to_string(), "greeting items about Bye, you!");
+
+		template.set_translations(translations.clone());
+		template.set_languages(&["de-AT", "en"]);
+		template.set_escaping(Escaping::Html);
+		let expected = "Hallo, &lt;Anna&gt;! 21 items About Fragments Bye, you!";
+		assert_eq!(template.to_string(), expected);
+
+		template.compile();
+		assert_eq!(template.to_string(), expected);
+		assert_eq!(template.to_source(), source.replace("\"you\"", "you"));
+
+		{
+			let mut shell = template.wrap();
+			shell.set_languages(&["ru", "en"]);
+
+			for &(count, expected) in [(1, "1 предмет"), (22, "22 предмета"), (11, "11 предметов")].iter() {
+				shell.insert("count".to_owned(), count);
+				assert!(shell.to_string().contains(expected));
+			}
+
+			shell.set_languages(&["en"]);
+			shell.insert("count".to_owned(), 0);
+			assert!(shell.to_string().contains(" No items "));
+			shell.insert("count".to_owned(), 1);
+			assert!(shell.to_string().contains(" One item "));
+			shell.insert("count".to_owned(), 1.5);
+			assert!(shell.to_string().contains(" 1.5 items "));
+		}
+
+		let mut template = monitored_from_str("[[~greeting name=:first]]");
+		assert_eq!(template.check().missing_content, vec!["first".to_owned()]);
+		template.insert("first".to_owned(), "Anna");
+		assert!(template.check().is_ok());
+
+		assert_eq!(PluralCategory::of("en", "1"), PluralCategory::One);
+		assert_eq!(PluralCategory::of("en", "1.0"), PluralCategory::Other);
+		assert_eq!(PluralCategory::of("fr", "1.5"), PluralCategory::One);
+		assert_eq!(PluralCategory::of("ru-RU", "12"), PluralCategory::Many);
+		assert_eq!(PluralCategory::of("pl", "22"), PluralCategory::Few);
+		assert_eq!(PluralCategory::of("ar", "102"), PluralCategory::Other);
+		assert_eq!(PluralCategory::of("ja", "1"), PluralCategory::Other);
+
+		assert!(Template::from_chars("[[~]]".chars()).is_err());
+		assert!(Template::from_chars("[[~greeting name]]".chars()).is_err());
+		assert!(Catalog::from_fluent("en", "items = { $n ->\n [one] One\n}").is_err());
+		assert!(Catalog::from_po("en", "msgid \"a\"\nmsgstr \"{b\"").is_err());
+	}
+
+	#[test]
+	fn extraction() {
+		let mut extractor = Extractor::new();
+		extractor.add_source("a.html", "<h1>[[~title]]</h1>\n[[?admin]]\n\t[[~items count=:n]][[/]]").unwrap();
+		extractor.add_source("b.html", "Welcome\n\n[[~title name=:name]]\n").unwrap();
+		assert!(extractor.add_source("c.html", "\n[[~]]").unwrap_err().starts_with("c.html:2: "));
+
+		let pot = extractor.to_string();
+		assert!(pot.contains("\n#. arguments: name\n#: a.html:1 b.html:3\nmsgid \"title\"\nmsgstr \"\"\n"));
+		assert!(pot.contains("\n#: a.html:3\nmsgid \"items\"\nmsgid_plural \"items\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n"));
+		assert!(!pot.contains("Welcome"));
+		assert!(Catalog::from_po("en", &pot).unwrap().is_empty());
+
+		let template = monitored_from_str("<h1>[[~title]]</h1>\n[[?admin]][[~items count=:n]][[/]]");
+		let ids: Vec<String> = template.translations().into_iter().map(|usage| usage.label).collect();
+		assert_eq!(ids, vec!["title".to_owned(), "items".to_owned()]);
+
+		let mut extractor = Extractor::new();
+		extractor.set_strings(true);
+		extractor.add_source("b.html", "Welcome\n\n[[~title name=:name]]\n \n\n  Bye \"you\"  [[:x]]-").unwrap();
+		let ids: Vec<(&str, usize)> = extractor.messages().iter().map(|message| (&*message.id, message.references[0].line)).collect();
+		assert_eq!(ids, vec![("Welcome", 1), ("title", 3), ("Bye \"you\"", 6)]);
+		assert!(extractor.to_string().contains("msgid \"Bye \\\"you\\\"\"\n"));
+
+		let catalog = Catalog::from_fluent("en", "title = Title\nold = Old\nwelcome = Welcome { -brand } { footer }\n-brand = Fragments\nfooter = Footer").unwrap();
+		assert_eq!(extractor.stale(&catalog), vec!["old", "welcome"]);
+	}
+
+	#[test]
+	fn lazy_content() {
+		let calls = AtomicUsize::new(0);
+		let count = || calls.fetch_add(1, Ordering::SeqCst) + 1;
+
+		let mut template = monitored_from_str("[[?debug]][[:report]][[/]]<[[:name|format >4]]>[[:name]]");
+		template.set_escaping(Escaping::Html);
+		template.insert("report".to_owned(), Lazy::new(|| -> String { count(); "report".to_owned() }));
+		template.insert("name".to_owned(), Lazy::memoized(|| format!("&{}", count())));
+		assert_eq!(template.to_string(), "<  &amp;1>&amp;1".to_owned());
+		assert_eq!(template.to_string(), "<  &amp;2>&amp;2".to_owned());
+
+		template.set("debug".to_owned(), true);
+		template.compile();
+		assert_eq!(template.to_string(), "report<  &amp;4>&amp;4".to_owned());
+		assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+		let mut shell = template.wrap();
+		shell.insert("report".to_owned(), Lazy::new(|| Safe("<b>override</b>")));
+		assert_eq!(shell.to_string(), "<b>override</b><  &amp;5>&amp;5".to_owned());
+	}
+
+	#[test]
+	fn resolvers() {
+		let config = |label: &str| match label {
+			"site" => Some(ContentType::StringSlice("Fragments")),
+			"price" => Some(ContentType::Float(2.5)),
+			"beta" => Some(ContentType::Bool(true)),
+			_ => None
+		};
+
+		let mut template = monitored_from_str("[[:site]] [[:price|format .2]] [[:name]][[?beta]] beta[[/]][[?:site]] site[[/]][[?!:other]] other[[/]] [[~\"hi {name}\" name=:site]]");
+		template.insert("name".to_owned(), PETER);
+		template.set_resolver(config);
+		assert_eq!(template.to_string(), "Fragments 2.50 Peter beta site other hi Fragments".to_owned());
+		template.compile();
+		assert_eq!(template.to_string(), "Fragments 2.50 Peter beta site other hi Fragments".to_owned());
+
+		let mut shell = template.wrap();
+		shell.unset("site".to_owned());
+		shell.set("beta".to_owned(), false);
+		assert_eq!(shell.to_string(), " 2.50 Peter other hi ".to_owned());
+
+		shell.set_resolver(|label: &str| if label == "price" { Some(3) } else { None });
+		assert_eq!(shell.to_string(), " 3.00 Peter other hi ".to_owned());
+
+		let mut template = monitored_from_str("[[?site]][[/]][[:site]][[?:site]][[:site|format >10]][[/]][[?beta]][[?:beta]] beta[[/]][[/]]");
+		assert_eq!(template.check().missing_content, vec!["site".to_owned()]);
+		assert_eq!(template.check().unset_conditions, vec!["site".to_owned(), "beta".to_owned()]);
+		template.set_resolver(config);
+		assert_eq!(template.check().missing_content, Vec::<String>::new());
+		assert_eq!(template.check().unset_conditions, Vec::<String>::new());
+
+		let calls = Arc::new(AtomicUsize::new(0));
+		let counter = calls.clone();
+		template.set_resolver(move |label: &str| {
+			counter.fetch_add(1, Ordering::SeqCst);
+			config(label)
+		});
+
+		let expected = "Fragments Fragments beta".to_owned();
+		assert_eq!(template.to_string(), expected);
+		let interpreted = calls.swap(0, Ordering::SeqCst);
+		template.compile();
+		assert_eq!(template.to_string(), expected);
+		assert_eq!(calls.load(Ordering::SeqCst), interpreted);
+		assert_eq!(interpreted, 5);
+
+		//Cargo sets the `CARGO_PKG_` variables for the tests
+		let environment = Environment::with_prefix("CARGO_PKG_");
+		assert_eq!(environment.variable("db.host-name"), "CARGO_PKG_DB_HOST_NAME");
+		assert!(!environment.resolve_condition("name"));
+		assert!(environment.is_defined("name"));
+
+		let mut template = monitored_from_str("[[?:name]]Built by [[:name]][[/]][[?name]] on purpose[[/]]");
+		template.set_resolver(Arc::new(environment));
+		assert_eq!(template.to_string(), format!("Built by {}", env!("CARGO_PKG_NAME")));
+	}
+
+	#[test]
+	fn dotted_paths() {
+		let mut address = HashMap::new();
+		address.insert("city".to_owned(), ContentType::StringSlice("Stockholm"));
+		let mut user = HashMap::new();
+		user.insert("name".to_owned(), ContentType::StringSlice(PETER));
+		user.insert("admin".to_owned(), ContentType::Bool(true));
+		user.insert("address".to_owned(), ContentType::Map(address));
+		user.insert("tags".to_owned(), ContentType::List(vec![ContentType::Char('a'), ContentType::Char('b')]));
+
+		let source = "[[:user.name]] lives in [[:user.address.city]][[?:user.address.street]], [[:user.address.street]][[/]].[[?user.admin]] (admin)[[/]] [[:user.tags.1]]";
+		let mut template = monitored_from_str(source);
+		template.insert("user".to_owned(), ContentType::Map(user));
+		assert_eq!(template.to_string(), "Peter lives in Stockholm. (admin) b".to_owned());
+		assert!(template.check().is_ok());
+
+		{
+			let mut shell = template.wrap();
+			shell.insert("user.address.street".to_owned(), "Drottninggatan");
+			shell.insert("user.name".to_owned(), "Paul");
+			shell.set("user.admin".to_owned(), false);
+			assert_eq!(shell.to_string(), "Paul lives in Stockholm, Drottninggatan. b".to_owned());
+		}
+
+		{
+			let mut shell = template.wrap();
+			shell.unset("user.address".to_owned());
+			assert_eq!(shell.to_string(), "Peter lives in . (admin) b".to_owned());
+		}
+
+		let specialized = template.specialize(&template);
+		assert_eq!(specialized.to_source(), "Peter lives in Stockholm[[?:user.address.street]], [[:user.address.street]][[/]]. (admin) b".to_owned());
+
+		template.compile();
+		let compiled = template.get_compiled().clone();
+		let mut context = Context::for_template(&compiled);
+		let mut order = HashMap::new();
+		order.insert("name".to_owned(), "Mary");
+		context.insert("user".to_owned(), order);
+		context.insert("user.address.city".to_owned(), "Uppsala");
+		assert_eq!(compiled.render(&context).to_string(), "Mary lives in Uppsala. ".to_owned());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn fill_serialized() {
+		use std::collections::BTreeMap;
+
+		#[derive(Serialize)]
+		struct Order {
+			customer: Customer,
+			items: Vec<&'static str>,
+			discount: Option<f64>,
+			express: bool,
+			status: Status
+		}
+
+		#[derive(Serialize)]
+		struct Customer {
+			name: &'static str,
+			visits: u32
+		}
+
+		#[derive(Serialize)]
+		#[allow(dead_code)]
+		enum Status {
+			Pending,
+			Shipped
+		}
+
+		let order = Order {
+			customer: Customer {
+				name: PETER,
+				visits: 3
+			},
+			items: vec!["a", "b", "c"],
+			discount: None,
+			express: true,
+			status: Status::Pending
+		};
+
+		let source = "[[:customer.name]] ([[:customer.visits]]): [[:items]], [[:items.1]][[?:discount]], [[:discount]][[/]][[?express]], express[[/]], [[:status]]";
+		let mut template = monitored_from_str(source);
+		assert_eq!(template.fill_serialized(&order), Ok(()));
+		assert_eq!(template.to_string(), "Peter (3): abc, b, express, Pending".to_owned());
+
+		let mut map = BTreeMap::new();
+		map.insert("customer", BTreeMap::new());
+		map.get_mut("customer").unwrap().insert("name", "Paul");
+		let compiled = template.get_compiled().clone();
+		let mut context = Context::for_template(&compiled);
+		assert_eq!(context.fill_serialized(&map), Ok(()));
+		assert_eq!(compiled.render(&context).to_string(), "Paul (): , , ".to_owned());
+
+		assert!(template.fill_serialized(&vec![1, 2, 3]).is_err());
+
+		#[derive(Serialize)]
+		struct Visitors {
+			people: Vec<Customer>
+		}
+
+		let visitors = Visitors {
+			people: vec![Customer { name: PETER, visits: 3 }, Customer { name: "Olivia", visits: 1 }]
+		};
+
+		let mut template = monitored_from_str("[[:people.1.name]]");
+		assert_eq!(template.fill_serialized(&visitors), Ok(()));
+		let people = template.get_content("people").unwrap();
+		assert_eq!(people.get_path("0.name").map(|v| v.to_string()), Some("Peter".to_owned()));
+		assert_eq!(people.get_path("1.visits").map(|v| v.to_string()), Some("1".to_owned()));
+		assert_eq!(template.to_string(), "Olivia".to_owned());
+	}
+
+	#[test]
+	fn compiled_instructions() {
+		let mut template = monitored_from_str("a[[?x]]b[[?!:y]]c[[/]][[:y]][[/]][[+g 1]][[:y]]");
+		template.compile();
+		let program = template.get_program().unwrap();
+		assert_eq!(program.get_instructions(), &[
+			Instruction::Text("a".to_owned()),
+			Instruction::Condition(0, true, 4),
+			Instruction::Text("b".to_owned()),
+			Instruction::ContentCondition(1, false, 1),
+			Instruction::Text("c".to_owned()),
+			Instruction::Content(1),
+			Instruction::Generate(2, vec!["1".to_owned().into()]),
+			Instruction::Content(1)
+		][..]);
+		assert_eq!(program.get_symbols().labels(), &["x".to_owned(), "y".to_owned(), "g".to_owned()][..]);
+	}
+
+	#[test]
+	fn compiled_rendering() {
+		let mut template = monitored_from_str("Hello[[?:name]], [[:name]][[/name]]![[?!:name]] I don't know you.[[/!name]][[?condition]] [[+echo a b]][[/]]");
+		template.insert_generator("echo".to_owned(), echo);
+		template.compile();
+		assert_eq!(template.to_string(), "Hello! I don't know you.".to_owned());
+		template.insert("name".to_owned(), PETER);
+		template.set("condition".to_owned(), true);
+		assert_eq!(template.to_string(), "Hello, Peter! a:b".to_owned());
+	}
+
 	test_insert!(1u8, 1u16, 1u32, 1u64, 1i8, 1i16, 1i32, 1i64, 'A', true, false);
 
 	#[test]
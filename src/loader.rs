@@ -0,0 +1,607 @@
+#[cfg(target_os = "linux")]
+use inotify::Inotify;
+use super::{Token, Span, Spanned, Template, CompiledTemplate, Fold, Escaping};
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+///A problem with one of the templates in a `TemplateSet`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct LoadError {
+	///The name of the template, or the path of the file or directory that
+	///couldn't be read.
+	pub name: String,
+	///A description of the problem.
+	pub message: String
+}
+
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: {}", self.name, self.message)
+	}
+}
+
+///A collection of named templates, where the templates can include each other.
+///
+///Include tokens, like `[[>header]]`, are replaced by the tokens of the
+///named template when a template is inserted, and when any of the templates
+///it includes are replaced. Includes that can't be resolved are kept as
+///they are, and will not render anything.
+#[derive(Clone, Debug)]
+pub struct TemplateSet {
+	sources: HashMap<String, Arc<CompiledTemplate>>,
+	templates: HashMap<String, Arc<CompiledTemplate>>,
+	dependencies: HashMap<String, HashSet<String>>,
+	problems: HashMap<String, Vec<LoadError>>
+}
+
+impl Default for TemplateSet {
+	fn default() -> TemplateSet {
+		TemplateSet::new()
+	}
+}
+
+impl TemplateSet {
+	///Create an empty `TemplateSet`.
+	pub fn new() -> TemplateSet {
+		TemplateSet {
+			sources: HashMap::new(),
+			templates: HashMap::new(),
+			dependencies: HashMap::new(),
+			problems: HashMap::new()
+		}
+	}
+
+	///Insert a template, or replace an existing one, and resolve its includes.
+	///
+	///The templates that include it are updated as well, and any problems
+	///with missing templates or include cycles are returned.
+	pub fn insert(&mut self, name: String, template: CompiledTemplate) -> Result<(), Vec<LoadError>> {
+		self.replace(vec![], vec![(name, template)])
+	}
+
+	///Remove a template.
+	///
+	///The templates that include it are updated, and the problems with the
+	///now missing template are returned.
+	pub fn remove(&mut self, name: &str) -> Result<(), Vec<LoadError>> {
+		self.replace(vec![name.to_owned()], vec![])
+	}
+
+	///Get a template with its includes resolved.
+	#[inline]
+	pub fn get(&self, name: &str) -> Option<&Arc<CompiledTemplate>> {
+		self.templates.get(name)
+	}
+
+	///Get a new `Template` around a template in the set.
+	#[inline]
+	pub fn template<'c>(&self, name: &str) -> Option<Template<'c>> {
+		self.templates.get(name).map(|template| Template::from_compiled(template.clone()))
+	}
+
+	///Get the template as it was inserted, without resolved includes.
+	#[inline]
+	pub fn get_source(&self, name: &str) -> Option<&Arc<CompiledTemplate>> {
+		self.sources.get(name)
+	}
+
+	///Check if there is a template with the name `name`.
+	#[inline]
+	pub fn contains(&self, name: &str) -> bool {
+		self.templates.contains_key(name)
+	}
+
+	///The names of all the templates, in sorted order.
+	pub fn names(&self) -> Vec<&str> {
+		let mut names: Vec<&str> = self.templates.keys().map(|name| &**name).collect();
+		names.sort();
+		names
+	}
+
+	///The number of templates in the set.
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.templates.len()
+	}
+
+	///Check if the set has no templates.
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.templates.is_empty()
+	}
+
+	///Remove and insert multiple templates before their includes are resolved.
+	fn replace(&mut self, removed: Vec<String>, inserted: Vec<(String, CompiledTemplate)>) -> Result<(), Vec<LoadError>> {
+		let mut changed = Vec::new();
+
+		for name in removed.into_iter() {
+			if self.sources.remove(&name).is_some() {
+				self.templates.remove(&name);
+				self.dependencies.remove(&name);
+				self.problems.remove(&name);
+				changed.push(name);
+			}
+		}
+
+		for (name, template) in inserted.into_iter() {
+			self.sources.insert(name.clone(), Arc::new(template));
+			changed.push(name);
+		}
+
+		let affected = self.dependents(&changed);
+		self.link(affected)
+	}
+
+	///The names in `names` and of every template that includes any of them,
+	///directly or indirectly, or tried to include them while they were missing.
+	fn dependents(&self, names: &[String]) -> Vec<String> {
+		let mut dependents = names.to_vec();
+		dependents.extend(self.dependencies.iter()
+			.filter(|&(_, dependencies)| names.iter().any(|name| dependencies.contains(name)))
+			.map(|(dependent, _)| dependent.clone())
+		);
+		dependents.sort();
+		dependents.dedup();
+		dependents
+	}
+
+	///Resolve the includes of the templates in `names`.
+	fn link(&mut self, names: Vec<String>) -> Result<(), Vec<LoadError>> {
+		let mut errors = Vec::new();
+
+		for name in names.into_iter() {
+			let source = match self.sources.get(&name) {
+				Some(source) => source.clone(),
+				None => continue
+			};
+
+			let (template, dependencies, messages) = {
+				let mut linker = Linker {
+					sources: &self.sources,
+					stack: vec![name.clone()],
+					dependencies: HashSet::new(),
+					errors: Vec::new()
+				};
+				let template = source.fold(&mut linker);
+				(template, linker.dependencies, linker.errors)
+			};
+
+			let problems: Vec<LoadError> = messages.into_iter().map(|message| LoadError {
+				name: name.clone(),
+				message
+			}).collect();
+
+			if problems.is_empty() {
+				self.problems.remove(&name);
+			} else {
+				errors.extend(problems.iter().cloned());
+				self.problems.insert(name.clone(), problems);
+			}
+
+			self.templates.insert(name.clone(), Arc::new(template));
+			self.dependencies.insert(name, dependencies);
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors)
+		}
+	}
+}
+
+///Replaces include tokens with the tokens of the included templates.
+struct Linker<'a> {
+	sources: &'a HashMap<String, Arc<CompiledTemplate>>,
+	stack: Vec<String>,
+	dependencies: HashSet<String>,
+	errors: Vec<String>
+}
+
+impl<'a> Fold for Linker<'a> {
+	fn fold_include(&mut self, name: String, span: Span) -> Vec<Spanned<Token>> {
+		self.dependencies.insert(name.clone());
+
+		if self.stack.contains(&name) {
+			self.errors.push(format!("include cycle: {} -> {}", self.stack.join(" -> "), name));
+			return vec![Spanned::new(Token::Include(name), span)];
+		}
+
+		let source = match self.sources.get(&name) {
+			Some(source) => source.clone(),
+			None => {
+				self.errors.push(format!("the included template '{}' at byte {} doesn't exist", name, span.start));
+				return vec![Spanned::new(Token::Include(name), span)];
+			}
+		};
+
+		self.stack.push(name);
+		let tokens = self.fold_tokens(source.get_tokens().to_vec());
+		self.stack.pop();
+		tokens
+	}
+}
+
+
+
+///Loads every template in a directory tree into a `TemplateSet`.
+///
+///Each file with the chosen extension is parsed and named after its path,
+///relative to the root directory, with `/` as separator and without the
+///extension. The file `emails/welcome.html` will, for example, be named
+///`emails/welcome` if the extension is `html`. Include tokens use the same
+///names.
+///
+///The templates are escaped as described by `Escaping::for_extension`,
+///unless something else is chosen with `set_escaping`.
+#[derive(Clone, Debug)]
+pub struct Loader {
+	root: PathBuf,
+	extension: String,
+	escaping: Escaping
+}
+
+impl Loader {
+	///Create a `Loader` for the files with the extension `extension` in
+	///the directory `root`. The extension is given without the leading `.`.
+	pub fn new<P: AsRef<Path>>(root: P, extension: &str) -> Loader {
+		Loader {
+			root: root.as_ref().to_path_buf(),
+			extension: extension.to_owned(),
+			escaping: Escaping::for_extension(extension)
+		}
+	}
+
+	///Set the escaping of the loaded templates.
+	pub fn set_escaping(&mut self, escaping: Escaping) {
+		self.escaping = escaping;
+	}
+
+	///The root directory of the templates.
+	#[inline]
+	pub fn root(&self) -> &Path {
+		&self.root
+	}
+
+	///Load and parse every template in the directory tree.
+	///
+	///All the files are read and parsed before any errors are returned, so
+	///every problem is reported at once.
+	pub fn load(&self) -> Result<TemplateSet, Vec<LoadError>> {
+		let mut errors = Vec::new();
+		let mut templates = Vec::new();
+		let mut set = TemplateSet::new();
+
+		for (name, path) in self.find_files(&mut errors).0.into_iter() {
+			match self.parse(&path) {
+				Ok(template) => templates.push((name, template)),
+				Err(message) => errors.push(LoadError {
+					name,
+					message
+				})
+			}
+		}
+
+		if let Err(link_errors) = set.replace(vec![], templates) {
+			errors.extend(link_errors);
+		}
+
+		if errors.is_empty() {
+			Ok(set)
+		} else {
+			errors.sort_by(|a, b| a.name.cmp(&b.name));
+			Err(errors)
+		}
+	}
+
+	///Load the templates and keep watching the directory tree for changes.
+	///
+	///This is meant for development, where templates are edited while the
+	///program is running. Changes are detected using inotify on Linux, and by
+	///looking for changed files every second on other systems, or if inotify
+	///isn't available.
+	pub fn watch(&self) -> Watcher {
+		Watcher::new(self.clone(), Backend::new())
+	}
+
+	///Load the templates and keep looking for changed files in the directory
+	///tree, at most once every `interval`.
+	///
+	///This works like `watch`, but without inotify.
+	pub fn poll(&self, interval: Duration) -> Watcher {
+		Watcher::new(self.clone(), Backend::Polling(interval))
+	}
+
+	///Get the name of the template at `path`, if it's a template file in the
+	///directory tree.
+	pub fn name_of(&self, path: &Path) -> Option<String> {
+		if path.extension().and_then(|e| e.to_str()) != Some(&*self.extension) {
+			return None;
+		}
+
+		let relative = match path.strip_prefix(&self.root) {
+			Ok(relative) => relative.with_extension(""),
+			Err(_) => return None
+		};
+
+		let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+		parts.map(|parts| parts.join("/"))
+	}
+
+	///Read and parse the template at `path`.
+	fn parse(&self, path: &Path) -> Result<CompiledTemplate, String> {
+		let file = match File::open(path) {
+			Ok(file) => file,
+			Err(e) => return Err(format!("io error: {}", e))
+		};
+
+		let mut template = CompiledTemplate::from_buffer(BufReader::new(file))?;
+		template.set_escaping(self.escaping.clone());
+		Ok(template)
+	}
+
+	///Find the template files in the directory tree, sorted by name, and
+	///every directory in the tree. Symbolic links to directories are
+	///followed, but each directory is only visited once, so links back up
+	///the tree don't make it endless.
+	fn find_files(&self, errors: &mut Vec<LoadError>) -> (Vec<(String, PathBuf)>, Vec<PathBuf>) {
+		let mut files = Vec::new();
+		let mut found_directories = Vec::new();
+		let mut visited = HashSet::new();
+		let mut directories = vec![self.root.clone()];
+
+		while let Some(directory) = directories.pop() {
+			if !visited.insert(fs::canonicalize(&directory).unwrap_or_else(|_| directory.clone())) {
+				continue;
+			}
+
+			found_directories.push(directory.clone());
+
+			let entries = match fs::read_dir(&directory) {
+				Ok(entries) => entries,
+				Err(e) => {
+					errors.push(LoadError {
+						name: directory.display().to_string(),
+						message: format!("io error: {}", e)
+					});
+					continue;
+				}
+			};
+
+			for entry in entries {
+				let path = match entry {
+					Ok(entry) => entry.path(),
+					Err(e) => {
+						errors.push(LoadError {
+							name: directory.display().to_string(),
+							message: format!("io error: {}", e)
+						});
+						continue;
+					}
+				};
+
+				if path.is_dir() {
+					directories.push(path);
+				} else if let Some(name) = self.name_of(&path) {
+					files.push((name, path));
+				}
+			}
+		}
+
+		files.sort_by(|a, b| a.0.cmp(&b.0));
+		(files, found_directories)
+	}
+}
+
+
+
+///A `TemplateSet` that is updated when the template files are changed.
+///
+///The directory tree is checked for changes when templates are requested,
+///so the templates are always up to date. A changed file is parsed again,
+///and the templates that include it are updated. The last good version of a
+///template is kept if a changed file can't be parsed, and the problem is
+///reported by `update` and `errors` until it's fixed.
+pub struct Watcher {
+	loader: Loader,
+	state: Mutex<WatchState>
+}
+
+struct WatchState {
+	set: TemplateSet,
+	backend: Backend,
+	files: HashMap<String, FileState>,
+	parse_errors: HashMap<String, LoadError>,
+	last_scan: Option<Instant>
+}
+
+#[derive(PartialEq)]
+struct FileState {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+	len: u64
+}
+
+enum Backend {
+	#[cfg(target_os = "linux")]
+	Inotify(Inotify),
+	Polling(Duration)
+}
+
+impl Backend {
+	#[cfg(target_os = "linux")]
+	fn new() -> Backend {
+		match Inotify::new() {
+			Some(inotify) => Backend::Inotify(inotify),
+			None => Backend::Polling(Duration::from_secs(1))
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	fn new() -> Backend {
+		Backend::Polling(Duration::from_secs(1))
+	}
+
+	///Check if it's time to scan the files, and which files are known to have
+	///changed. Polling doesn't know of any particular files.
+	fn changes(&mut self, last_scan: Instant) -> Option<HashSet<PathBuf>> {
+		match *self {
+			#[cfg(target_os = "linux")]
+			Backend::Inotify(ref mut inotify) => inotify.read_events(),
+			Backend::Polling(interval) if last_scan.elapsed() >= interval => Some(HashSet::new()),
+			Backend::Polling(_) => None
+		}
+	}
+
+	///Start watching the directories, falling back to polling if it fails.
+	#[cfg(target_os = "linux")]
+	fn watch(&mut self, directories: &[PathBuf]) {
+		let watched = match *self {
+			Backend::Inotify(ref mut inotify) => {
+				inotify.retain(directories);
+				directories.iter().all(|directory| inotify.watch(directory))
+			},
+			Backend::Polling(_) => true
+		};
+
+		if !watched {
+			*self = Backend::Polling(Duration::from_secs(1));
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	fn watch(&mut self, _directories: &[PathBuf]) {}
+}
+
+impl Watcher {
+	fn new(loader: Loader, backend: Backend) -> Watcher {
+		let watcher = Watcher {
+			loader,
+			state: Mutex::new(WatchState {
+				set: TemplateSet::new(),
+				backend,
+				files: HashMap::new(),
+				parse_errors: HashMap::new(),
+				last_scan: None
+			})
+		};
+
+		watcher.update();
+		watcher
+	}
+
+	///Check for changes now, and return the problems with the changed
+	///templates and the templates that include them.
+	///
+	///It's called automatically by `get` and `template`.
+	pub fn update(&self) -> Vec<LoadError> {
+		let mut state = self.state.lock().unwrap();
+
+		let changed = match state.last_scan {
+			Some(last_scan) => state.backend.changes(last_scan),
+			None => Some(HashSet::new())
+		};
+
+		match changed {
+			Some(changed) => self.scan(&mut state, &changed),
+			None => vec![]
+		}
+	}
+
+	///Get a template with its includes resolved.
+	pub fn get(&self, name: &str) -> Option<Arc<CompiledTemplate>> {
+		self.update();
+		self.state.lock().unwrap().set.get(name).cloned()
+	}
+
+	///Get a new `Template` around one of the templates.
+	pub fn template<'c>(&self, name: &str) -> Option<Template<'c>> {
+		self.get(name).map(Template::from_compiled)
+	}
+
+	///Every current problem with the templates, sorted by name.
+	pub fn errors(&self) -> Vec<LoadError> {
+		let state = self.state.lock().unwrap();
+		let mut errors: Vec<LoadError> = state.parse_errors.values().cloned().collect();
+
+		for problems in state.set.problems.values() {
+			errors.extend(problems.iter().cloned());
+		}
+
+		errors.sort_by(|a, b| a.name.cmp(&b.name));
+		errors
+	}
+
+	///Check if files are looked for instead of watched with inotify.
+	pub fn is_polling(&self) -> bool {
+		match self.state.lock().unwrap().backend {
+			Backend::Polling(_) => true,
+			#[cfg(target_os = "linux")]
+			Backend::Inotify(_) => false
+		}
+	}
+
+	///Look for added and removed files, and parse the files in `changed`
+	///and the files that look different since the last scan.
+	fn scan(&self, state: &mut WatchState, changed: &HashSet<PathBuf>) -> Vec<LoadError> {
+		let mut errors = Vec::new();
+		let (found, directories) = self.loader.find_files(&mut errors);
+		state.backend.watch(&directories);
+		state.last_scan = Some(Instant::now());
+
+		let removed: Vec<String> = state.files.keys()
+			.filter(|name| !found.iter().any(|(found, _)| found == *name))
+			.cloned()
+			.collect();
+		let mut inserted = Vec::new();
+
+		for name in removed.iter() {
+			state.files.remove(name);
+			state.parse_errors.remove(name);
+		}
+
+		for (name, path) in found.into_iter() {
+			let file = match fs::metadata(&path) {
+				Ok(metadata) => FileState {
+					modified: metadata.modified().ok(),
+					len: metadata.len(),
+					path
+				},
+				Err(_) => continue
+			};
+
+			if state.files.get(&name) == Some(&file) && !changed.contains(&file.path) {
+				continue;
+			}
+
+			match self.loader.parse(&file.path) {
+				Ok(template) => {
+					state.parse_errors.remove(&name);
+					inserted.push((name.clone(), template));
+				},
+				Err(message) => {
+					let error = LoadError {
+						name: name.clone(),
+						message
+					};
+					errors.push(error.clone());
+					state.parse_errors.insert(name.clone(), error);
+				}
+			}
+
+			state.files.insert(name, file);
+		}
+
+		if let Err(problems) = state.set.replace(removed, inserted) {
+			errors.extend(problems);
+		}
+
+		errors
+	}
+}
@@ -0,0 +1,142 @@
+///Conventions for writing numbers, currencies and percentages.
+///
+///A locale is used by the format specs `n`, `c` and `%`, like
+///`[[:total:c]]`, and it's set with `set_locale` on a `Template`, `Context`
+///or `Shell`. The patterns use `#` for the number and `¤` for the currency
+///symbol, and the sign is written before the pattern.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Locale {
+	///The mark between the integer part and the decimals.
+	pub decimal: String,
+	///The separator between groups of three digits in the integer part.
+	pub group: String,
+	///The currency symbol, like `$` or `€`.
+	pub currency: String,
+	///The currency pattern, like `¤#` or `# ¤`.
+	pub currency_pattern: String,
+	///The percent pattern, like `#%` or `# %`.
+	pub percent_pattern: String
+}
+
+///Built-in locales: tag, decimal mark, group separator, currency symbol,
+///currency pattern and percent pattern.
+const LOCALES: &[(&str, &str, &str, &str, &str, &str)] = &[
+	("en", ".", ",", "$", "¤#", "#%"),
+	("en-us", ".", ",", "$", "¤#", "#%"),
+	("en-gb", ".", ",", "£", "¤#", "#%"),
+	("en-ie", ".", ",", "€", "¤#", "#%"),
+	("de", ",", ".", "€", "# ¤", "# %"),
+	("de-de", ",", ".", "€", "# ¤", "# %"),
+	("de-at", ",", " ", "€", "¤ #", "# %"),
+	("de-ch", ".", "’", "CHF", "¤ #", "#%"),
+	("fr", ",", " ", "€", "# ¤", "# %"),
+	("fr-fr", ",", " ", "€", "# ¤", "# %"),
+	("fr-ch", ",", " ", "CHF", "# ¤", "# %"),
+	("es", ",", ".", "€", "# ¤", "# %"),
+	("es-es", ",", ".", "€", "# ¤", "# %"),
+	("it", ",", ".", "€", "# ¤", "#%"),
+	("it-it", ",", ".", "€", "# ¤", "#%"),
+	("nl", ",", ".", "€", "¤ #", "#%"),
+	("nl-nl", ",", ".", "€", "¤ #", "#%"),
+	("pt", ",", ".", "€", "# ¤", "#%"),
+	("pt-br", ",", ".", "R$", "¤ #", "#%"),
+	("sv", ",", " ", "kr", "# ¤", "# %"),
+	("sv-se", ",", " ", "kr", "# ¤", "# %"),
+	("nb", ",", " ", "kr", "# ¤", "# %"),
+	("da", ",", ".", "kr.", "# ¤", "# %"),
+	("fi", ",", " ", "€", "# ¤", "# %"),
+	("pl", ",", " ", "zł", "# ¤", "#%"),
+	("ja", ".", ",", "¥", "¤#", "#%"),
+	("zh", ".", ",", "¥", "¤#", "#%")
+];
+
+impl Locale {
+	///Find a built-in locale by its language tag, like `en-US` or `de`. A
+	///tag with an unknown region falls back to the language.
+	pub fn from_tag(tag: &str) -> Option<Locale> {
+		let tag = tag.replace('_', "-").to_lowercase();
+		let language = tag.split('-').next().unwrap_or("");
+
+		LOCALES.iter()
+			.find(|locale| locale.0 == tag)
+			.or_else(|| LOCALES.iter().find(|locale| locale.0 == language))
+			.map(|&(_, decimal, group, currency, currency_pattern, percent_pattern)| Locale {
+				decimal: decimal.to_owned(),
+				group: group.to_owned(),
+				currency: currency.to_owned(),
+				currency_pattern: currency_pattern.to_owned(),
+				percent_pattern: percent_pattern.to_owned()
+			})
+	}
+
+	///Use `currency` as currency symbol, like `EUR` or `€`.
+	pub fn with_currency(mut self, currency: &str) -> Locale {
+		self.currency = currency.to_owned();
+		self
+	}
+
+	///Write a number, like `-1234.5`, with the decimal mark and group
+	///separators of the locale. Anything that isn't a plain decimal number is
+	///left as it is.
+	pub fn format_number(&self, number: &str) -> String {
+		let (sign, digits) = split_sign(number);
+		let (integer, decimals) = match digits.find('.') {
+			Some(index) => (&digits[..index], Some(&digits[index + 1..])),
+			None => (digits, None)
+		};
+
+		if integer.is_empty() || !integer.bytes().all(|b| b.is_ascii_digit()) {
+			return number.to_owned();
+		}
+
+		let mut result = sign.to_owned();
+
+		for (index, c) in integer.chars().enumerate() {
+			if index > 0 && (integer.len() - index) % 3 == 0 {
+				result.push_str(&self.group);
+			}
+
+			result.push(c);
+		}
+
+		if let Some(decimals) = decimals {
+			result.push_str(&self.decimal);
+			result.push_str(decimals);
+		}
+
+		result
+	}
+
+	///Write a number, like `-1234.5`, as an amount in the currency of the locale.
+	pub fn format_currency(&self, number: &str) -> String {
+		self.apply(&self.currency_pattern, number)
+	}
+
+	///Write a number, like `12.5`, as a percentage. The number is not multiplied by 100.
+	pub fn format_percent(&self, number: &str) -> String {
+		self.apply(&self.percent_pattern, number)
+	}
+
+	fn apply(&self, pattern: &str, number: &str) -> String {
+		let formatted = self.format_number(number);
+		let (sign, formatted) = split_sign(&formatted);
+		let mut result = sign.to_owned();
+		result.push_str(&pattern.replace('¤', &self.currency).replace('#', formatted));
+		result
+	}
+}
+
+impl Default for Locale {
+	///The default locale is `en-US`.
+	fn default() -> Locale {
+		Locale::from_tag("en-US").unwrap()
+	}
+}
+
+fn split_sign(number: &str) -> (&str, &str) {
+	if number.starts_with('-') || number.starts_with('+') {
+		number.split_at(1)
+	} else {
+		("", number)
+	}
+}
@@ -0,0 +1,129 @@
+//!Formatting of floating point numbers.
+
+use std::fmt::Write;
+
+///How many fractional digits a formatted float has.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SignificantDigits {
+	///As many digits as it takes to represent the number exactly.
+	DigAll,
+	///At most this many digits, where trailing zeros are left out.
+	DigMax(usize),
+	///Exactly this many digits.
+	DigExact(usize)
+}
+
+///How the exponent of a formatted float is written.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExponentFormat {
+	///No exponent, like `1200`.
+	ExpNone,
+	///A base 10 exponent, like `1.2e3`.
+	ExpDec,
+	///A base 2 exponent, like `1.171875p10`.
+	ExpBin
+}
+
+///Format `value` with `digits` fractional digits and an `exponent`.
+///
+///Numbers are rounded to the nearest representable number of digits. Not a
+///number is written as `NaN`, the infinities as `inf` and `-inf`. Negative
+///zero, and negative numbers that are rounded to zero, are written without
+///a sign, so `-0.004` with two digits becomes `0.00`.
+pub fn format_float(value: f64, digits: SignificantDigits, exponent: ExponentFormat) -> String {
+	if value.is_nan() {
+		return "NaN".to_owned();
+	} else if value.is_infinite() {
+		return if value < 0.0 { "-inf" } else { "inf" }.to_owned();
+	}
+
+	//adding positive zero turns negative zero into positive zero
+	let value = value + 0.0;
+
+	match exponent {
+		ExponentFormat::ExpNone => unsigned_zero(match digits {
+			SignificantDigits::DigAll => format!("{}", value),
+			SignificantDigits::DigMax(n) => trim_fraction(format!("{:.*}", n, value)),
+			SignificantDigits::DigExact(n) => format!("{:.*}", n, value)
+		}),
+		ExponentFormat::ExpDec => {
+			let formatted = match digits {
+				SignificantDigits::DigAll => format!("{:e}", value),
+				SignificantDigits::DigMax(n) | SignificantDigits::DigExact(n) => format!("{:.*e}", n, value)
+			};
+
+			match digits {
+				SignificantDigits::DigMax(_) => {
+					let e = formatted.find('e').unwrap_or(formatted.len());
+					let mut mantissa = trim_fraction(formatted[..e].to_owned());
+					mantissa.push_str(&formatted[e..]);
+					mantissa
+				},
+				_ => formatted
+			}
+		},
+		ExponentFormat::ExpBin => {
+			let (mantissa, exponent) = binary_parts(value);
+			let mut formatted = format_float(mantissa, digits, ExponentFormat::ExpNone);
+			write!(formatted, "p{}", exponent).unwrap();
+			formatted
+		}
+	}
+}
+
+///Widen an `f32` to an `f64` with the same shortest decimal representation,
+///so that `1.2f32` is still written as `1.2`.
+pub fn widen(value: f32) -> f64 {
+	if value.is_finite() {
+		value.to_string().parse().unwrap_or(value as f64)
+	} else {
+		value as f64
+	}
+}
+
+///Remove the sign from a negative number that was rounded to zero.
+fn unsigned_zero(formatted: String) -> String {
+	if formatted.starts_with('-') && formatted[1..].chars().all(|c| c == '0' || c == '.') {
+		formatted[1..].to_owned()
+	} else {
+		formatted
+	}
+}
+
+///Remove trailing zeros after the decimal point, as well as the point itself
+///if nothing is left after it.
+fn trim_fraction(mut formatted: String) -> String {
+	if formatted.contains('.') {
+		let trimmed = formatted.trim_end_matches('0').trim_end_matches('.').len();
+		formatted.truncate(trimmed);
+	}
+
+	formatted
+}
+
+///Split a finite `value` into a mantissa, where `1 <= |mantissa| < 2`, and a
+///base 2 exponent. Zero has the mantissa and exponent 0.
+fn binary_parts(value: f64) -> (f64, i32) {
+	if value == 0.0 {
+		return (0.0, 0);
+	}
+
+	let mut exponent = value.abs().log2().floor() as i32;
+	let mut mantissa = if exponent < -1000 {
+		//2^exponent would underflow for subnormal numbers
+		value * 2f64.powi(64) / 2f64.powi(exponent + 64)
+	} else {
+		value / 2f64.powi(exponent)
+	};
+
+	//log2 may be off by one close to powers of two
+	if mantissa.abs() >= 2.0 {
+		mantissa /= 2.0;
+		exponent += 1;
+	} else if mantissa.abs() < 1.0 {
+		mantissa *= 2.0;
+		exponent -= 1;
+	}
+
+	(mantissa, exponent)
+}
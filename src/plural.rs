@@ -0,0 +1,396 @@
+///A CLDR plural category, which decides the form of a word for a number.
+///
+///The categories of a number depend on the language. English only uses
+///`One` and `Other`, while Russian uses `One`, `Few`, `Many` and `Other`
+///and Japanese only uses `Other`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PluralCategory {
+	Zero,
+	One,
+	Two,
+	Few,
+	Many,
+	Other
+}
+
+impl PluralCategory {
+	///Find the category of a number, written like `1` or `2.50`, in the
+	///language of a tag, like `en` or `pt-PT`.
+	///
+	///Visible decimals are part of the number, so `1.0` may belong to an
+	///other category than `1`. Languages without built-in rules use the
+	///English rules, and anything that isn't a number is `Other`.
+	pub fn of(language: &str, number: &str) -> PluralCategory {
+		let tag = language.replace('_', "-").to_lowercase();
+		let language = tag.split('-').next().unwrap_or("");
+
+		let o = match Operands::parse(number) {
+			Some(operands) => operands,
+			None => return PluralCategory::Other
+		};
+
+		let i10 = o.i % 10;
+		let i100 = o.i % 100;
+
+		match language {
+			"ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" | "lo" | "my" | "km" => PluralCategory::Other,
+			"fr" | "pt" if o.i <= 1 && tag != "pt-pt" => PluralCategory::One,
+			"fr" | "pt" | "es" | "it" | "ca" if o.is_millions() => PluralCategory::Many,
+			"es" if o.n == 1.0 => PluralCategory::One,
+			"pt" if o.i == 1 && o.v == 0 => PluralCategory::One,
+			"fr" | "pt" | "es" => PluralCategory::Other,
+			"da" if o.n == 1.0 || (o.t != 0 && o.i <= 1) => PluralCategory::One,
+			"hu" | "tr" | "el" | "bg" if o.n == 1.0 => PluralCategory::One,
+			"da" | "hu" | "tr" | "el" | "bg" => PluralCategory::Other,
+			"ru" | "uk" | "be" if o.v == 0 => match (i10, i100) {
+				(1, i100) if i100 != 11 => PluralCategory::One,
+				(2..=4, i100) if !(12..=14).contains(&i100) => PluralCategory::Few,
+				_ => PluralCategory::Many
+			},
+			"pl" if o.v == 0 => match (o.i, i10, i100) {
+				(1, _, _) => PluralCategory::One,
+				(_, 2..=4, i100) if !(12..=14).contains(&i100) => PluralCategory::Few,
+				_ => PluralCategory::Many
+			},
+			"ru" | "uk" | "be" | "pl" => PluralCategory::Other,
+			"cs" | "sk" => match (o.i, o.v) {
+				(1, 0) => PluralCategory::One,
+				(2..=4, 0) => PluralCategory::Few,
+				(_, 0) => PluralCategory::Other,
+				_ => PluralCategory::Many
+			},
+			"hr" | "sr" | "bs" => {
+				let f10 = o.f % 10;
+				let f100 = o.f % 100;
+
+				if (o.v == 0 && i10 == 1 && i100 != 11) || (f10 == 1 && f100 != 11) {
+					PluralCategory::One
+				} else if (o.v == 0 && (2..=4).contains(&i10) && !(12..=14).contains(&i100)) || ((2..=4).contains(&f10) && !(12..=14).contains(&f100)) {
+					PluralCategory::Few
+				} else {
+					PluralCategory::Other
+				}
+			},
+			"ar" => match o.integer() {
+				Some(0) => PluralCategory::Zero,
+				Some(1) => PluralCategory::One,
+				Some(2) => PluralCategory::Two,
+				Some(n) if (3..=10).contains(&(n % 100)) => PluralCategory::Few,
+				Some(n) if (11..=99).contains(&(n % 100)) => PluralCategory::Many,
+				_ => PluralCategory::Other
+			},
+			"he" => match (o.i, o.v) {
+				(1, 0) | (0, 1..) => PluralCategory::One,
+				(2, 0) => PluralCategory::Two,
+				_ => PluralCategory::Other
+			},
+			_ if o.i == 1 && o.v == 0 => PluralCategory::One,
+			_ => PluralCategory::Other
+		}
+	}
+
+	///Find a category by its CLDR name, like `one` or `other`.
+	pub fn from_name(name: &str) -> Option<PluralCategory> {
+		match name {
+			"zero" => Some(PluralCategory::Zero),
+			"one" => Some(PluralCategory::One),
+			"two" => Some(PluralCategory::Two),
+			"few" => Some(PluralCategory::Few),
+			"many" => Some(PluralCategory::Many),
+			"other" => Some(PluralCategory::Other),
+			_ => None
+		}
+	}
+
+	///The CLDR name of the category, like `one` or `other`.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			PluralCategory::Zero => "zero",
+			PluralCategory::One => "one",
+			PluralCategory::Two => "two",
+			PluralCategory::Few => "few",
+			PluralCategory::Many => "many",
+			PluralCategory::Other => "other"
+		}
+	}
+}
+
+///The CLDR plural operands of a number.
+struct Operands {
+	///The absolute value.
+	n: f64,
+	///The integer digits.
+	i: u64,
+	///The number of visible decimals.
+	v: usize,
+	///The visible decimals.
+	f: u64,
+	///The visible decimals, without trailing zeros.
+	t: u64
+}
+
+impl Operands {
+	fn parse(number: &str) -> Option<Operands> {
+		let number = number.trim().trim_start_matches(['-', '+']);
+		let (integer, decimals) = match number.find('.') {
+			Some(index) => (&number[..index], &number[index + 1..]),
+			None => (number, "")
+		};
+
+		let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+		if integer.is_empty() || !digits(integer) || !digits(decimals) {
+			return None;
+		}
+
+		let trimmed = decimals.trim_end_matches('0');
+
+		Some(Operands {
+			n: number.parse().ok()?,
+			i: integer.parse().ok()?,
+			v: decimals.len(),
+			f: if decimals.is_empty() { 0 } else { decimals.parse().ok()? },
+			t: if trimmed.is_empty() { 0 } else { trimmed.parse().ok()? }
+		})
+	}
+
+	///The value, if it's a whole number.
+	fn integer(&self) -> Option<u64> {
+		if self.f == 0 {
+			Some(self.i)
+		} else {
+			None
+		}
+	}
+
+	///Whole millions, like `1000000`, use `Many` in some languages.
+	fn is_millions(&self) -> bool {
+		self.v == 0 && self.i != 0 && self.i.is_multiple_of(1000000)
+	}
+}
+
+
+
+///The plural forms of a gettext catalog, from a `Plural-Forms` header like
+///`nplurals=2; plural=(n != 1);`.
+///
+///The expression uses the C syntax for `n`, numbers, `!`, `*`, `/`, `%`,
+///`+`, `-`, comparisons, `&&`, `||`, `?:` and parentheses, and its result is
+///the index of the form.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PluralForms {
+	count: usize,
+	expression: Expression
+}
+
+impl PluralForms {
+	///Parse the value of a `Plural-Forms` header.
+	pub fn parse(header: &str) -> Result<PluralForms, String> {
+		let mut count = None;
+		let mut expression = None;
+
+		for part in header.split(';') {
+			let (key, value) = match part.find('=') {
+				Some(index) => (part[..index].trim(), part[index + 1..].trim()),
+				None if part.trim().is_empty() => continue,
+				None => return Err(format!("expected 'key=value' in plural forms, but found '{}'", part.trim()))
+			};
+
+			match key {
+				"nplurals" => count = Some(value.parse::<usize>().map_err(|_| format!("invalid number of plural forms: '{}'", value))?),
+				"plural" => expression = Some(Expression::parse(value)?),
+				_ => {}
+			}
+		}
+
+		match (count, expression) {
+			(Some(0), _) => Err("there must be at least one plural form".to_owned()),
+			(Some(count), Some(expression)) => Ok(PluralForms {
+				count,
+				expression
+			}),
+			(None, _) => Err("missing 'nplurals' in plural forms".to_owned()),
+			(_, None) => Err("missing 'plural' in plural forms".to_owned())
+		}
+	}
+
+	///The index of the form for `n`.
+	pub fn index(&self, n: u64) -> usize {
+		let index = self.expression.eval(n);
+		if index < self.count as u64 {
+			index as usize
+		} else {
+			0
+		}
+	}
+}
+
+impl Default for PluralForms {
+	///The English forms, `nplurals=2; plural=(n != 1);`.
+	fn default() -> PluralForms {
+		PluralForms {
+			count: 2,
+			expression: Expression::Binary(Operator::NotEqual, Box::new(Expression::N), Box::new(Expression::Number(1)))
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Operator {
+	Or,
+	And,
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+	Remainder
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expression {
+	N,
+	Number(u64),
+	Not(Box<Expression>),
+	Binary(Operator, Box<Expression>, Box<Expression>),
+	Conditional(Box<Expression>, Box<Expression>, Box<Expression>)
+}
+
+///Operators from the lowest to the highest precedence.
+const OPERATORS: &[&[(&str, Operator)]] = &[
+	&[("||", Operator::Or)],
+	&[("&&", Operator::And)],
+	&[("==", Operator::Equal), ("!=", Operator::NotEqual)],
+	&[("<=", Operator::LessEqual), (">=", Operator::GreaterEqual), ("<", Operator::Less), (">", Operator::Greater)],
+	&[("+", Operator::Add), ("-", Operator::Subtract)],
+	&[("*", Operator::Multiply), ("/", Operator::Divide), ("%", Operator::Remainder)]
+];
+
+impl Expression {
+	fn parse(source: &str) -> Result<Expression, String> {
+		let mut rest = source.trim();
+		let expression = parse_conditional(&mut rest)?;
+
+		if rest.is_empty() {
+			Ok(expression)
+		} else {
+			Err(format!("unexpected '{}' in plural expression", rest))
+		}
+	}
+
+	fn eval(&self, n: u64) -> u64 {
+		match *self {
+			Expression::N => n,
+			Expression::Number(value) => value,
+			Expression::Not(ref e) => (e.eval(n) == 0) as u64,
+			Expression::Conditional(ref condition, ref then, ref otherwise) => if condition.eval(n) != 0 {
+				then.eval(n)
+			} else {
+				otherwise.eval(n)
+			},
+			Expression::Binary(operator, ref a, ref b) => {
+				let a = a.eval(n);
+				let b = b.eval(n);
+
+				match operator {
+					Operator::Or => (a != 0 || b != 0) as u64,
+					Operator::And => (a != 0 && b != 0) as u64,
+					Operator::Equal => (a == b) as u64,
+					Operator::NotEqual => (a != b) as u64,
+					Operator::Less => (a < b) as u64,
+					Operator::LessEqual => (a <= b) as u64,
+					Operator::Greater => (a > b) as u64,
+					Operator::GreaterEqual => (a >= b) as u64,
+					Operator::Add => a.wrapping_add(b),
+					Operator::Subtract => a.wrapping_sub(b),
+					Operator::Multiply => a.wrapping_mul(b),
+					Operator::Divide => a.checked_div(b).unwrap_or(0),
+					Operator::Remainder => a.checked_rem(b).unwrap_or(0)
+				}
+			}
+		}
+	}
+}
+
+fn eat(rest: &mut &str, token: &str) -> bool {
+	if rest.starts_with(token) {
+		*rest = rest[token.len()..].trim_start();
+		true
+	} else {
+		false
+	}
+}
+
+fn parse_conditional(rest: &mut &str) -> Result<Expression, String> {
+	let condition = parse_binary(rest, 0)?;
+
+	if !eat(rest, "?") {
+		return Ok(condition);
+	}
+
+	let then = parse_conditional(rest)?;
+
+	if !eat(rest, ":") {
+		return Err("expected ':' in plural expression".to_owned());
+	}
+
+	let otherwise = parse_conditional(rest)?;
+	Ok(Expression::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+}
+
+fn parse_binary(rest: &mut &str, level: usize) -> Result<Expression, String> {
+	let operators = match OPERATORS.get(level) {
+		Some(operators) => operators,
+		None => return parse_unary(rest)
+	};
+
+	let mut expression = parse_binary(rest, level + 1)?;
+
+	'operands: loop {
+		for &(token, operator) in operators.iter() {
+			if eat(rest, token) {
+				let operand = parse_binary(rest, level + 1)?;
+				expression = Expression::Binary(operator, Box::new(expression), Box::new(operand));
+				continue 'operands;
+			}
+		}
+
+		return Ok(expression);
+	}
+}
+
+fn parse_unary(rest: &mut &str) -> Result<Expression, String> {
+	if eat(rest, "!") {
+		return Ok(Expression::Not(Box::new(parse_unary(rest)?)));
+	}
+
+	if eat(rest, "(") {
+		let expression = parse_conditional(rest)?;
+
+		return if eat(rest, ")") {
+			Ok(expression)
+		} else {
+			Err("expected ')' in plural expression".to_owned())
+		};
+	}
+
+	if eat(rest, "n") {
+		return Ok(Expression::N);
+	}
+
+	let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+	if digits > 0 {
+		let value = rest[..digits].parse().map_err(|_| format!("invalid number '{}' in plural expression", &rest[..digits]))?;
+		*rest = rest[digits..].trim_start();
+		return Ok(Expression::Number(value));
+	}
+
+	match rest.chars().next() {
+		Some(c) => Err(format!("unexpected '{}' in plural expression", c)),
+		None => Err("unexpected end of plural expression".to_owned())
+	}
+}
@@ -0,0 +1,217 @@
+use super::{Token, Filter, Argument, Spanned, Lookup, ContentType, Generator, Symbols, Label, Escaping};
+use escape;
+use filter;
+use catalog;
+use lazy;
+use resolver;
+
+use std::fmt;
+use std::sync::Arc;
+
+///A single step in a compiled `Program`.
+///
+///Labels are replaced by slot indices into the symbol table of the
+///program and conditional blocks are replaced by forward jumps.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Instruction {
+	///Write a string.
+	Text(String),
+	///Write the content of a content slot.
+	Content(usize),
+	///Write the content of a content slot through filters.
+	Filtered(usize, Vec<Filter>),
+	///Skip the given number of instructions if the condition in the slot is not the expected value.
+	Condition(usize, bool, usize),
+	///Skip the given number of instructions if the content slot being defined is not the expected value.
+	ContentCondition(usize, bool, usize),
+	///Run the generator in a generator slot with the given arguments.
+	Generate(usize, Vec<Spanned<String>>),
+	///Write a translated message. The labels of the arguments are looked up by name.
+	Translate(String, Vec<Argument>)
+}
+
+///A template token tree, lowered into a flat list of instructions.
+///
+///Each label is resolved to a slot when the program is compiled, so a
+///label is looked up at most once per rendering, no matter how many times
+///it's used in the template.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Program {
+	instructions: Vec<Instruction>,
+	symbols: Arc<Symbols>
+}
+
+impl Program {
+	///Compile a token tree into a `Program`.
+	pub fn compile(tokens: &[Spanned<Token>]) -> Program {
+		Program::compile_with(tokens, Arc::new(Symbols::from_tokens(tokens)))
+	}
+
+	///Compile a token tree into a `Program`, using an existing symbol table.
+	///
+	///This will panic if a label in `tokens` is missing from `symbols`.
+	pub fn compile_with(tokens: &[Spanned<Token>], symbols: Arc<Symbols>) -> Program {
+		let mut program = Program {
+			instructions: Vec::new(),
+			symbols
+		};
+
+		program.lower(tokens);
+		program
+	}
+
+	///The instructions of the program.
+	pub fn get_instructions(&self) -> &[Instruction] {
+		&self.instructions
+	}
+
+	///The symbol table of the program.
+	pub fn get_symbols(&self) -> &Arc<Symbols> {
+		&self.symbols
+	}
+
+	///Render the program, using the content, conditions and generators of `template`.
+	pub fn render(&self, template: &dyn Lookup, f: &mut fmt::Formatter) -> fmt::Result {
+		self.render_escaped(template, &Escaping::None, f)
+	}
+
+	///Render the program, like `render`, and escape the content with `escaping`.
+	pub fn render_escaped(&self, template: &dyn Lookup, escaping: &Escaping, f: &mut fmt::Formatter) -> fmt::Result {
+		let _render = lazy::RenderScope::enter();
+		let slots = self.symbols.len();
+		let labels = self.symbols.labels();
+		let mut content: Vec<Option<Option<&ContentType>>> = vec![None; slots];
+		let mut conditions: Vec<Option<bool>> = vec![None; slots];
+		let mut generators: Vec<Option<Option<&dyn Generator>>> = vec![None; slots];
+		let mut defined: Vec<Option<bool>> = vec![None; slots];
+		let mut resolved: Vec<Option<Option<ContentType>>> = (0..slots).map(|_| None).collect();
+
+		let mut position = 0;
+
+		while let Some(instruction) = self.instructions.get(position) {
+			position += 1;
+
+			match instruction {
+				Instruction::Text(s) => f.write_str(s)?,
+
+				&Instruction::Content(slot) => {
+					let value = resolve(&mut content, slot, || template.get_content_at(self.label(slot), &labels[slot]));
+					match value {
+						Some(value) => escape::write_content(value, escaping, f)?,
+						None => if let Some(value) = resolved[slot].get_or_insert_with(|| resolver::content(template, &labels[slot])) {
+							escape::write_content(value, escaping, f)?;
+						}
+					}
+				},
+
+				&Instruction::Filtered(slot, ref filters) => {
+					let value = resolve(&mut content, slot, || template.get_content_at(self.label(slot), &labels[slot]));
+					match value {
+						Some(value) => filter::write_filtered(value, filters, escaping, template.get_locale(), template.get_now(), f)?,
+						None => if let Some(value) = resolved[slot].get_or_insert_with(|| resolver::content(template, &labels[slot])) {
+							filter::write_filtered(value, filters, escaping, template.get_locale(), template.get_now(), f)?;
+						}
+					}
+				},
+
+				&Instruction::Condition(slot, expected, skip) => {
+					let value = resolve(&mut conditions, slot, || {
+						template.get_condition_at(self.label(slot), &labels[slot]) || resolver::condition(template, &labels[slot])
+					});
+					if value != expected {
+						position += skip;
+					}
+				},
+
+				&Instruction::ContentCondition(slot, expected, skip) => {
+					let value = resolve(&mut defined, slot, || {
+						template.is_content_defined_at(self.label(slot), &labels[slot]) || resolver::is_defined(template, &labels[slot])
+					});
+					if value != expected {
+						position += skip;
+					}
+				},
+
+				&Instruction::Generate(slot, ref args) => {
+					let gen = resolve(&mut generators, slot, || template.get_generator(&labels[slot]));
+					if let Some(gen) = gen {
+						gen.generate(args, f)?;
+					}
+				},
+
+				Instruction::Translate(id, args) => catalog::write_translation(template, id, args, escaping, f)?
+			}
+		}
+
+		Ok(())
+	}
+
+	#[inline]
+	fn label(&self, slot: usize) -> Label {
+		self.symbols.label(slot).expect("instruction slot is outside the symbol table")
+	}
+
+	fn slot_for(&self, label: &str) -> usize {
+		match self.symbols.get(label) {
+			Some(label) => label.index(),
+			None => panic!("the label '{}' is missing from the symbol table", label)
+		}
+	}
+
+	fn lower(&mut self, tokens: &[Spanned<Token>]) {
+		for token in tokens.iter() {
+			match &token.node {
+				Token::String(s) => self.instructions.push(Instruction::Text(s.clone())),
+
+				Token::Placeholder(k) => {
+					let slot = self.slot_for(k);
+					self.instructions.push(Instruction::Content(slot));
+				},
+
+				Token::Filtered(k, filters) => {
+					let slot = self.slot_for(k);
+					self.instructions.push(Instruction::Filtered(slot, filters.clone()));
+				},
+
+				&Token::Conditional(ref k, expected, ref tokens) => {
+					let slot = self.slot_for(k);
+					let jump = self.instructions.len();
+					self.instructions.push(Instruction::Condition(slot, expected, 0));
+					self.lower(tokens);
+					let skip = self.instructions.len() - jump - 1;
+					self.instructions[jump] = Instruction::Condition(slot, expected, skip);
+				},
+
+				&Token::ContentConditional(ref k, expected, ref tokens) => {
+					let slot = self.slot_for(k);
+					let jump = self.instructions.len();
+					self.instructions.push(Instruction::ContentCondition(slot, expected, 0));
+					self.lower(tokens);
+					let skip = self.instructions.len() - jump - 1;
+					self.instructions[jump] = Instruction::ContentCondition(slot, expected, skip);
+				},
+
+				Token::Generated(k, args) => {
+					let slot = self.slot_for(k);
+					self.instructions.push(Instruction::Generate(slot, args.clone()));
+				},
+
+				//Includes are resolved by a `TemplateSet`, and render nothing otherwise
+				&Token::Include(_) => {},
+
+				Token::Translated(id, args) => self.instructions.push(Instruction::Translate(id.clone(), args.clone()))
+			}
+		}
+	}
+}
+
+fn resolve<T: Copy, F: FnOnce() -> T>(cache: &mut [Option<T>], slot: usize, lookup: F) -> T {
+	match cache[slot] {
+		Some(value) => value,
+		None => {
+			let value = lookup();
+			cache[slot] = Some(value);
+			value
+		}
+	}
+}
@@ -0,0 +1,123 @@
+use super::{ContentType, TemplateContent, Lookup};
+
+use std::env;
+use std::sync::Arc;
+
+///A fallback for labels that have no content or conditions.
+///
+///A resolver is consulted when a placeholder, a condition or a translation
+///argument can't be found among the content and conditions of a template,
+///so content can be pulled on demand from somewhere else, like a cache,
+///the environment or a configuration file. Each of `resolve`,
+///`resolve_condition` and `is_defined` is called at most once per label in a
+///rendering, whether the template is compiled or not. Translation arguments
+///are resolved each time they are used.
+///
+///Functions and closures with the signature `Fn(&str) -> Option<T>`, where
+///`T` is any `'static` template content, are resolvers.
+pub trait Resolver: Send + Sync {
+	///The content for `label`, or `None` if it's unknown.
+	fn resolve(&self, label: &str) -> Option<ContentType<'static>>;
+
+	///The value of the condition `label`. It's true if the content for
+	///`label` is `true`, by default.
+	fn resolve_condition(&self, label: &str) -> bool {
+		match self.resolve(label) {
+			Some(ContentType::Bool(value)) => value,
+			_ => false
+		}
+	}
+
+	///Check if there's content for `label`.
+	fn is_defined(&self, label: &str) -> bool {
+		self.resolve(label).is_some()
+	}
+}
+
+impl<F, T> Resolver for F where
+	F: Fn(&str) -> Option<T> + Send + Sync,
+	T: TemplateContent<'static>
+{
+	fn resolve(&self, label: &str) -> Option<ContentType<'static>> {
+		(*self)(label).map(TemplateContent::into_template_content)
+	}
+}
+
+impl<R: Resolver + ?Sized> Resolver for Arc<R> {
+	fn resolve(&self, label: &str) -> Option<ContentType<'static>> {
+		(**self).resolve(label)
+	}
+
+	fn resolve_condition(&self, label: &str) -> bool {
+		(**self).resolve_condition(label)
+	}
+
+	fn is_defined(&self, label: &str) -> bool {
+		(**self).is_defined(label)
+	}
+}
+
+///Resolves labels from environment variables.
+///
+///A label is turned into the name of a variable by making it upper case
+///and replacing `.` and `-` with `_`, after an optional prefix. The label
+///`db.host` is read from `APP_DB_HOST` if the prefix is `APP_`. Conditions
+///are true if the variable is `1`, `true`, `yes` or `on`, in any case.
+#[derive(Clone, Default, Debug)]
+pub struct Environment {
+	prefix: String
+}
+
+impl Environment {
+	///Resolve labels from variables without a prefix.
+	pub fn new() -> Environment {
+		Environment {
+			prefix: String::new()
+		}
+	}
+
+	///Resolve labels from variables that start with `prefix`.
+	pub fn with_prefix<S: Into<String>>(prefix: S) -> Environment {
+		Environment {
+			prefix: prefix.into()
+		}
+	}
+
+	///The name of the variable for `label`.
+	pub fn variable(&self, label: &str) -> String {
+		let mut name = self.prefix.clone();
+		name.extend(label.chars().map(|c| match c {
+			'.' | '-' => '_',
+			c => c.to_ascii_uppercase()
+		}));
+		name
+	}
+}
+
+impl Resolver for Environment {
+	fn resolve(&self, label: &str) -> Option<ContentType<'static>> {
+		env::var(self.variable(label)).ok().map(ContentType::String)
+	}
+
+	fn resolve_condition(&self, label: &str) -> bool {
+		match env::var(self.variable(label)) {
+			Ok(value) => ["1", "true", "yes", "on"].iter().any(|word| value.trim().eq_ignore_ascii_case(word)),
+			Err(_) => false
+		}
+	}
+}
+
+///The content for `label` from the resolver of `lookup`, if any.
+pub fn content(lookup: &dyn Lookup, label: &str) -> Option<ContentType<'static>> {
+	lookup.get_resolver(label).and_then(|resolver| resolver.resolve(label))
+}
+
+///The condition `label` from the resolver of `lookup`, if any.
+pub fn condition(lookup: &dyn Lookup, label: &str) -> bool {
+	lookup.get_resolver(label).map(|resolver| resolver.resolve_condition(label)).unwrap_or(false)
+}
+
+///Check if the resolver of `lookup`, if any, has content for `label`.
+pub fn is_defined(lookup: &dyn Lookup, label: &str) -> bool {
+	lookup.get_resolver(label).map(|resolver| resolver.is_defined(label)).unwrap_or(false)
+}
@@ -0,0 +1,363 @@
+use serde::ser::{self, Serialize};
+
+use super::{ContentType, Fill};
+use number;
+
+use std::error::Error as StdError;
+use std::fmt;
+
+///Fill `target` with the content and conditions in `value`.
+///
+///The value has to be serialized as a map or a struct, where the keys become
+///labels. Booleans become conditions, and other values become content.
+///Nested maps and structs provide labels with their key and a `.` as
+///prefix, like `address.city`. Sequences become lists, which are rendered
+///by rendering each item after each other, and each item is also available
+///with its index as label, like `items.0`. Maps and structs in sequences
+///become map content, so they can also be reached through the list, like
+///`people.0.name`. Missing values, like `None`, and empty sequences are left
+///out, so they can be checked with `[[?:label]]`.
+pub fn fill<'c, T: Serialize + ?Sized, F: Fill<'c>>(value: &T, target: &mut F) -> Result<(), String> {
+	match value.serialize(ValueSerializer) {
+		Ok(Value::Map(entries)) => {
+			for (key, value) in entries.into_iter() {
+				fill_value(key, value, target);
+			}
+			Ok(())
+		},
+		Ok(Value::Null) => Ok(()),
+		Ok(_) => Err("only maps and structs can fill a template".to_owned()),
+		Err(e) => Err(e.0)
+	}
+}
+
+fn fill_value<'c, F: Fill<'c>>(label: String, value: Value, target: &mut F) {
+	match value {
+		Value::Null => {},
+		Value::Bool(b) => target.fill_condition(label, b),
+		Value::List(items) => {
+			if items.is_empty() {
+				return;
+			}
+
+			let list = items.iter().filter_map(Value::to_content).collect();
+			target.fill_content(label.clone(), ContentType::List(list));
+
+			for (index, item) in items.into_iter().enumerate() {
+				fill_value(format!("{}.{}", label, index), item, target);
+			}
+		},
+		Value::Map(entries) => {
+			for (key, value) in entries.into_iter() {
+				fill_value(format!("{}.{}", label, key), value, target);
+			}
+		},
+		value => if let Some(content) = value.to_content() {
+			target.fill_content(label, content);
+		}
+	}
+}
+
+///A serialized value.
+enum Value {
+	Null,
+	Bool(bool),
+	Int(i64),
+	UnsignedInt(u64),
+	Float(f64),
+	Char(char),
+	String(String),
+	List(Vec<Value>),
+	Map(Vec<(String, Value)>)
+}
+
+impl Value {
+	///Convert a value to content, if it can be rendered.
+	fn to_content<'c>(&self) -> Option<ContentType<'c>> {
+		match *self {
+			Value::Null => None,
+			Value::Bool(b) => Some(ContentType::Bool(b)),
+			Value::Int(i) => Some(ContentType::Int(i)),
+			Value::UnsignedInt(u) => Some(ContentType::UnsignedInt(u)),
+			Value::Float(f) => Some(ContentType::Float(f)),
+			Value::Char(c) => Some(ContentType::Char(c)),
+			Value::String(ref s) => Some(ContentType::String(s.clone())),
+			Value::List(ref items) => Some(ContentType::List(items.iter().filter_map(Value::to_content).collect())),
+			Value::Map(ref entries) => Some(ContentType::Map(entries.iter()
+				.filter_map(|(key, value)| value.to_content().map(|content| (key.clone(), content)))
+				.collect()))
+		}
+	}
+
+	///Convert a value to a map key, if it's a string, a character or a number.
+	fn into_key(self) -> Result<String, Error> {
+		match self {
+			Value::String(s) => Ok(s),
+			Value::Char(c) => Ok(c.to_string()),
+			Value::Int(i) => Ok(i.to_string()),
+			Value::UnsignedInt(u) => Ok(u.to_string()),
+			Value::Bool(b) => Ok(b.to_string()),
+			_ => Err(Error("map keys must be strings, characters, numbers or booleans".to_owned()))
+		}
+	}
+}
+
+///An error from serializing a value.
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl StdError for Error {}
+
+impl ser::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error(msg.to_string())
+	}
+}
+
+///Serializes values into `Value`. Enums are represented like
+///`{"Variant": content}`, except for unit variants, which become strings.
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+	type Ok = Value;
+	type Error = Error;
+	type SerializeSeq = SeqBuilder;
+	type SerializeTuple = SeqBuilder;
+	type SerializeTupleStruct = SeqBuilder;
+	type SerializeTupleVariant = VariantBuilder<SeqBuilder>;
+	type SerializeMap = MapBuilder;
+	type SerializeStruct = MapBuilder;
+	type SerializeStructVariant = VariantBuilder<MapBuilder>;
+
+	fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+		Ok(Value::Bool(v))
+	}
+
+	fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+		Ok(Value::Int(v as i64))
+	}
+
+	fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+		Ok(Value::Int(v as i64))
+	}
+
+	fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+		Ok(Value::Int(v as i64))
+	}
+
+	fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+		Ok(Value::Int(v))
+	}
+
+	fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+		Ok(Value::UnsignedInt(v as u64))
+	}
+
+	fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+		Ok(Value::UnsignedInt(v as u64))
+	}
+
+	fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+		Ok(Value::UnsignedInt(v as u64))
+	}
+
+	fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+		Ok(Value::UnsignedInt(v))
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+		Ok(Value::Float(number::widen(v)))
+	}
+
+	fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+		Ok(Value::Float(v))
+	}
+
+	fn serialize_char(self, v: char) -> Result<Value, Error> {
+		Ok(Value::Char(v))
+	}
+
+	fn serialize_str(self, v: &str) -> Result<Value, Error> {
+		Ok(Value::String(v.to_owned()))
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+		Ok(Value::List(v.iter().map(|&b| Value::UnsignedInt(b as u64)).collect()))
+	}
+
+	fn serialize_none(self) -> Result<Value, Error> {
+		Ok(Value::Null)
+	}
+
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<Value, Error> {
+		Ok(Value::Null)
+	}
+
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+		Ok(Value::Null)
+	}
+
+	fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, Error> {
+		Ok(Value::String(variant.to_owned()))
+	}
+
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Value, Error> {
+		let value = value.serialize(self)?;
+		Ok(Value::Map(vec![(variant.to_owned(), value)]))
+	}
+
+	fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, Error> {
+		Ok(SeqBuilder(Vec::with_capacity(len.unwrap_or(0))))
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqBuilder, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<VariantBuilder<SeqBuilder>, Error> {
+		Ok(VariantBuilder(variant, self.serialize_seq(Some(len))?))
+	}
+
+	fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, Error> {
+		Ok(MapBuilder(Vec::with_capacity(len.unwrap_or(0)), None))
+	}
+
+	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapBuilder, Error> {
+		self.serialize_map(Some(len))
+	}
+
+	fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<VariantBuilder<MapBuilder>, Error> {
+		Ok(VariantBuilder(variant, self.serialize_map(Some(len))?))
+	}
+}
+
+struct SeqBuilder(Vec<Value>);
+
+impl SeqBuilder {
+	fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.0.push(value.serialize(ValueSerializer)?);
+		Ok(())
+	}
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::List(self.0))
+	}
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::List(self.0))
+	}
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.push(value)
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::List(self.0))
+	}
+}
+
+struct MapBuilder(Vec<(String, Value)>, Option<String>);
+
+impl ser::SerializeMap for MapBuilder {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+		self.1 = Some(key.serialize(ValueSerializer)?.into_key()?);
+		Ok(())
+	}
+
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		let key = self.1.take().ok_or_else(|| Error("a map value was serialized before its key".to_owned()))?;
+		self.0.push((key, value.serialize(ValueSerializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::Map(self.0))
+	}
+}
+
+impl ser::SerializeStruct for MapBuilder {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.0.push((key.to_owned(), value.serialize(ValueSerializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::Map(self.0))
+	}
+}
+
+///Wraps the content of an enum variant in a map with the name of the variant as key.
+struct VariantBuilder<T>(&'static str, T);
+
+impl ser::SerializeTupleVariant for VariantBuilder<SeqBuilder> {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		self.1.push(value)
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::Map(vec![(self.0.to_owned(), Value::List((self.1).0))]))
+	}
+}
+
+impl ser::SerializeStructVariant for VariantBuilder<MapBuilder> {
+	type Ok = Value;
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		ser::SerializeStruct::serialize_field(&mut self.1, key, value)
+	}
+
+	fn end(self) -> Result<Value, Error> {
+		Ok(Value::Map(vec![(self.0.to_owned(), Value::Map((self.1).0))]))
+	}
+}
@@ -0,0 +1,100 @@
+use super::{Token, Filter, Span, Spanned, Lookup, ContentType, Fold, Escaping};
+use check::DefinedLabels;
+use filter::Filtered;
+
+///Folds the parts of a token tree that are already known from a `Lookup`.
+///
+///Conditions that are explicitly set, or found in maps, are replaced by
+///their content or removed, and so are content conditions for labels with
+///content.
+///Placeholders with static content, like numbers and strings, are rendered
+///into strings. Nested templates, shells and `Fmt` values, and lists with
+///any of them, are kept as placeholders, since their output may change
+///between renderings. Inlined content is escaped with the escaping of the
+///template.
+pub struct Specializer<'a, 'c: 'a> {
+	lookup: &'a (dyn Lookup<'c> + 'a),
+	defined: DefinedLabels,
+	escaping: &'a Escaping
+}
+
+impl<'a, 'c> Specializer<'a, 'c> {
+	pub fn new(lookup: &'a (dyn Lookup<'c> + 'a), escaping: &'a Escaping) -> Specializer<'a, 'c> {
+		let mut defined = DefinedLabels::new();
+		lookup.collect_labels(&mut defined);
+
+		Specializer {
+			lookup,
+			defined,
+			escaping
+		}
+	}
+
+	fn static_content(&self, label: &str, filters: &[Filter]) -> Option<String> {
+		//relative dates depend on when the template is rendered
+		if !self.defined.has_content(label) || filters.iter().any(|filter| filter.name == "relative") {
+			return None;
+		}
+
+		match self.lookup.get_content(label) {
+			Some(content) if is_static(content) => Some(Filtered {
+				content,
+				filters,
+				escaping: self.escaping,
+				locale: self.lookup.get_locale(),
+				now: self.lookup.get_now()
+			}.to_string()),
+			_ => None
+		}
+	}
+}
+
+fn is_static(content: &ContentType) -> bool {
+	match *content {
+		ContentType::Template(_) | ContentType::Shell(_) | ContentType::Fmt(_) | ContentType::Lazy(_) => false,
+		ContentType::List(ref items) => items.iter().all(is_static),
+		ContentType::Safe(ref content) => is_static(content),
+		_ => true
+	}
+}
+
+impl<'a, 'c> Fold for Specializer<'a, 'c> {
+	fn fold_placeholder(&mut self, label: String, span: Span) -> Vec<Spanned<Token>> {
+		match self.static_content(&label, &[]) {
+			Some(s) => vec![Spanned::new(Token::String(s), span)],
+			None => vec![Spanned::new(Token::Placeholder(label), span)]
+		}
+	}
+
+	fn fold_filtered(&mut self, label: String, filters: Vec<Filter>, span: Span) -> Vec<Spanned<Token>> {
+		match self.static_content(&label, &filters) {
+			Some(s) => vec![Spanned::new(Token::String(s), span)],
+			None => vec![Spanned::new(Token::Filtered(label, filters), span)]
+		}
+	}
+
+	fn fold_conditional(&mut self, label: String, expected: bool, tokens: Vec<Spanned<Token>>, span: Span) -> Vec<Spanned<Token>> {
+		let value = match self.defined.conditions.get(&label).cloned() {
+			None if self.defined.has_content_prefix(&label) => Some(self.lookup.get_condition(&label)),
+			value => value
+		};
+
+		match value {
+			Some(value) if value == expected => self.fold_tokens(tokens),
+			Some(_) => vec![],
+			None => vec![Spanned::new(Token::Conditional(label, expected, self.fold_tokens(tokens)), span)]
+		}
+	}
+
+	fn fold_content_conditional(&mut self, label: String, expected: bool, tokens: Vec<Spanned<Token>>, span: Span) -> Vec<Spanned<Token>> {
+		if self.defined.has_content(&label) && self.lookup.is_content_defined(&label) {
+			if expected {
+				self.fold_tokens(tokens)
+			} else {
+				vec![]
+			}
+		} else {
+			vec![Spanned::new(Token::ContentConditional(label, expected, self.fold_tokens(tokens)), span)]
+		}
+	}
+}
@@ -0,0 +1,131 @@
+use super::{Token, Spanned};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_TABLE: AtomicUsize = AtomicUsize::new(0);
+
+///An interned label, belonging to the symbol table of a `CompiledTemplate`.
+///
+///A `Label` is resolved once, using `CompiledTemplate::label`, and can then
+///be used to set and read content in a `Context` without hashing the label.
+///Labels can only be used with contexts for the template they came from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Label {
+	table: usize,
+	index: usize
+}
+
+impl Label {
+	///The slot index of the label.
+	#[inline]
+	pub fn index(&self) -> usize {
+		self.index
+	}
+}
+
+///A table of every label that is used in a template.
+///
+///Each distinct label, whether it's used for content, conditions or
+///generators, gets its own slot in the order it first appears in.
+#[derive(PartialEq, Debug)]
+pub struct Symbols {
+	id: usize,
+	labels: Vec<String>,
+	indices: HashMap<String, usize>
+}
+
+impl Symbols {
+	///Collect the labels from a token tree.
+	pub fn from_tokens(tokens: &[Spanned<Token>]) -> Symbols {
+		let mut symbols = Symbols {
+			id: NEXT_TABLE.fetch_add(1, Ordering::Relaxed),
+			labels: Vec::new(),
+			indices: HashMap::new()
+		};
+
+		symbols.collect(tokens);
+		symbols
+	}
+
+	///Find the `Label` for `name`, if it's used in the template.
+	#[inline]
+	pub fn get(&self, name: &str) -> Option<Label> {
+		self.indices.get(name).map(|&index| Label {
+			table: self.id,
+			index
+		})
+	}
+
+	///Get the `Label` in slot `index`, if there is one.
+	#[inline]
+	pub fn label(&self, index: usize) -> Option<Label> {
+		if index < self.labels.len() {
+			Some(Label {
+				table: self.id,
+				index
+			})
+		} else {
+			None
+		}
+	}
+
+	///Get the name of `label`, if it belongs to this table.
+	#[inline]
+	pub fn name(&self, label: Label) -> Option<&str> {
+		if self.owns(label) {
+			Some(&self.labels[label.index])
+		} else {
+			None
+		}
+	}
+
+	///Check if `label` belongs to this table.
+	#[inline]
+	pub fn owns(&self, label: Label) -> bool {
+		label.table == self.id
+	}
+
+	///The labels in the table, in slot order.
+	#[inline]
+	pub fn labels(&self) -> &[String] {
+		&self.labels
+	}
+
+	///The number of labels in the table.
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.labels.len()
+	}
+
+	///Check if the table has no labels.
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.labels.is_empty()
+	}
+
+	fn collect(&mut self, tokens: &[Spanned<Token>]) {
+		for token in tokens.iter() {
+			match &token.node {
+				&Token::String(_) => {},
+				&Token::Placeholder(ref k) | &Token::Filtered(ref k, _) => self.intern(k),
+				&Token::Conditional(ref k, _, ref tokens) | &Token::ContentConditional(ref k, _, ref tokens) => {
+					self.intern(k);
+					self.collect(tokens);
+				},
+				Token::Generated(k, _) => self.intern(k),
+				&Token::Include(_) => {},
+				Token::Translated(_, args) => for label in args.iter().filter_map(|arg| arg.label()) {
+					self.intern(label);
+				}
+			}
+		}
+	}
+
+	fn intern(&mut self, name: &str) {
+		if !self.indices.contains_key(name) {
+			self.indices.insert(name.to_owned(), self.labels.len());
+			self.labels.push(name.to_owned());
+		}
+	}
+}
@@ -0,0 +1,231 @@
+use super::{Token, Filter, Argument, ArgumentValue, Span, Spanned};
+
+///A trait for walking through token trees.
+///
+///There's one method for each kind of token. The methods for conditional
+///blocks walk into the content of the blocks by default, translation tokens
+///visit the labels of their arguments as placeholders, while the other
+///methods do nothing. Override the methods to collect information about the
+///tokens, and call `walk_tokens` to keep walking into conditional blocks.
+pub trait Visitor {
+	fn visit_tokens(&mut self, tokens: &[Spanned<Token>]) {
+		walk_tokens(self, tokens)
+	}
+
+	fn visit_token(&mut self, token: &Spanned<Token>) {
+		walk_token(self, token)
+	}
+
+	fn visit_string(&mut self, _s: &str) {}
+
+	fn visit_placeholder(&mut self, _label: &str) {}
+
+	fn visit_filtered(&mut self, label: &str, _filters: &[Filter]) {
+		self.visit_placeholder(label)
+	}
+
+	fn visit_conditional(&mut self, _label: &str, _expected: bool, tokens: &[Spanned<Token>]) {
+		self.visit_tokens(tokens)
+	}
+
+	fn visit_content_conditional(&mut self, _label: &str, _expected: bool, tokens: &[Spanned<Token>]) {
+		self.visit_tokens(tokens)
+	}
+
+	fn visit_generated(&mut self, _label: &str, _args: &[Spanned<String>]) {}
+
+	fn visit_include(&mut self, _name: &str) {}
+
+	fn visit_translated(&mut self, _id: &str, args: &[Argument]) {
+		for label in args.iter().filter_map(Argument::label) {
+			self.visit_placeholder(label);
+		}
+	}
+}
+
+///Visit each token in `tokens`.
+pub fn walk_tokens<V: Visitor + ?Sized>(visitor: &mut V, tokens: &[Spanned<Token>]) {
+	for token in tokens.iter() {
+		visitor.visit_token(token);
+	}
+}
+
+///Call the method of `visitor` that matches the kind of `token`.
+pub fn walk_token<V: Visitor + ?Sized>(visitor: &mut V, token: &Spanned<Token>) {
+	match &token.node {
+		Token::String(s) => visitor.visit_string(s),
+		Token::Placeholder(k) => visitor.visit_placeholder(k),
+		Token::Filtered(k, filters) => visitor.visit_filtered(k, filters),
+		&Token::Conditional(ref k, expected, ref tokens) => visitor.visit_conditional(k, expected, tokens),
+		&Token::ContentConditional(ref k, expected, ref tokens) => visitor.visit_content_conditional(k, expected, tokens),
+		Token::Generated(k, args) => visitor.visit_generated(k, args),
+		Token::Include(k) => visitor.visit_include(k),
+		Token::Translated(id, args) => visitor.visit_translated(id, args)
+	}
+}
+
+
+
+///A trait for walking through token trees and changing the tokens in place.
+///
+///It works like `Visitor`, but with mutable access to each part of the tokens.
+pub trait VisitorMut {
+	fn visit_tokens_mut(&mut self, tokens: &mut Vec<Spanned<Token>>) {
+		walk_tokens_mut(self, tokens)
+	}
+
+	fn visit_token_mut(&mut self, token: &mut Spanned<Token>) {
+		walk_token_mut(self, token)
+	}
+
+	fn visit_string_mut(&mut self, _s: &mut String) {}
+
+	fn visit_placeholder_mut(&mut self, _label: &mut String) {}
+
+	fn visit_filtered_mut(&mut self, label: &mut String, _filters: &mut Vec<Filter>) {
+		self.visit_placeholder_mut(label)
+	}
+
+	fn visit_conditional_mut(&mut self, _label: &mut String, _expected: &mut bool, tokens: &mut Vec<Spanned<Token>>) {
+		self.visit_tokens_mut(tokens)
+	}
+
+	fn visit_content_conditional_mut(&mut self, _label: &mut String, _expected: &mut bool, tokens: &mut Vec<Spanned<Token>>) {
+		self.visit_tokens_mut(tokens)
+	}
+
+	fn visit_generated_mut(&mut self, _label: &mut String, _args: &mut Vec<Spanned<String>>) {}
+
+	fn visit_include_mut(&mut self, _name: &mut String) {}
+
+	fn visit_translated_mut(&mut self, _id: &mut String, args: &mut Vec<Argument>) {
+		for arg in args.iter_mut() {
+			if let ArgumentValue::Label(ref mut label) = arg.value {
+				self.visit_placeholder_mut(label);
+			}
+		}
+	}
+}
+
+///Visit each token in `tokens`, with mutable access.
+pub fn walk_tokens_mut<V: VisitorMut + ?Sized>(visitor: &mut V, tokens: &mut [Spanned<Token>]) {
+	for token in tokens.iter_mut() {
+		visitor.visit_token_mut(token);
+	}
+}
+
+///Call the method of `visitor` that matches the kind of `token`, with mutable access.
+pub fn walk_token_mut<V: VisitorMut + ?Sized>(visitor: &mut V, token: &mut Spanned<Token>) {
+	match token.node {
+		Token::String(ref mut s) => visitor.visit_string_mut(s),
+		Token::Placeholder(ref mut k) => visitor.visit_placeholder_mut(k),
+		Token::Filtered(ref mut k, ref mut filters) => visitor.visit_filtered_mut(k, filters),
+		Token::Conditional(ref mut k, ref mut expected, ref mut tokens) => visitor.visit_conditional_mut(k, expected, tokens),
+		Token::ContentConditional(ref mut k, ref mut expected, ref mut tokens) => visitor.visit_content_conditional_mut(k, expected, tokens),
+		Token::Generated(ref mut k, ref mut args) => visitor.visit_generated_mut(k, args),
+		Token::Include(ref mut k) => visitor.visit_include_mut(k),
+		Token::Translated(ref mut id, ref mut args) => visitor.visit_translated_mut(id, args)
+	}
+}
+
+
+
+///A trait for transforming token trees.
+///
+///Each token is folded into zero or more new tokens, which makes it possible
+///to remove tokens, replace them or inline the content of conditional blocks.
+///The methods for conditional blocks fold the content of the blocks by
+///default, while the other methods return the token as it is. Each method
+///gets the span of the folded token, to be used for the new tokens. Adjacent
+///strings are merged by `fold_tokens`.
+pub trait Fold {
+	fn fold_tokens(&mut self, tokens: Vec<Spanned<Token>>) -> Vec<Spanned<Token>> {
+		fold_tokens(self, tokens)
+	}
+
+	fn fold_token(&mut self, token: Spanned<Token>) -> Vec<Spanned<Token>> {
+		fold_token(self, token)
+	}
+
+	fn fold_string(&mut self, s: String, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::String(s), span)]
+	}
+
+	fn fold_placeholder(&mut self, label: String, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Placeholder(label), span)]
+	}
+
+	fn fold_filtered(&mut self, label: String, filters: Vec<Filter>, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Filtered(label, filters), span)]
+	}
+
+	fn fold_conditional(&mut self, label: String, expected: bool, tokens: Vec<Spanned<Token>>, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Conditional(label, expected, self.fold_tokens(tokens)), span)]
+	}
+
+	fn fold_content_conditional(&mut self, label: String, expected: bool, tokens: Vec<Spanned<Token>>, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::ContentConditional(label, expected, self.fold_tokens(tokens)), span)]
+	}
+
+	fn fold_generated(&mut self, label: String, args: Vec<Spanned<String>>, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Generated(label, args), span)]
+	}
+
+	fn fold_include(&mut self, name: String, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Include(name), span)]
+	}
+
+	fn fold_translated(&mut self, id: String, args: Vec<Argument>, span: Span) -> Vec<Spanned<Token>> {
+		vec![Spanned::new(Token::Translated(id, args), span)]
+	}
+}
+
+///Fold each token in `tokens` and merge adjacent strings in the result.
+pub fn fold_tokens<F: Fold + ?Sized>(folder: &mut F, tokens: Vec<Spanned<Token>>) -> Vec<Spanned<Token>> {
+	let mut result: Vec<Spanned<Token>> = Vec::with_capacity(tokens.len());
+
+	for token in tokens.into_iter() {
+		for new_token in folder.fold_token(token).into_iter() {
+			push_merged(&mut result, new_token);
+		}
+	}
+
+	result
+}
+
+///Call the method of `folder` that matches the kind of `token`.
+pub fn fold_token<F: Fold + ?Sized>(folder: &mut F, token: Spanned<Token>) -> Vec<Spanned<Token>> {
+	let span = token.span;
+
+	match token.node {
+		Token::String(s) => folder.fold_string(s, span),
+		Token::Placeholder(k) => folder.fold_placeholder(k, span),
+		Token::Filtered(k, filters) => folder.fold_filtered(k, filters, span),
+		Token::Conditional(k, expected, tokens) => folder.fold_conditional(k, expected, tokens, span),
+		Token::ContentConditional(k, expected, tokens) => folder.fold_content_conditional(k, expected, tokens, span),
+		Token::Generated(k, args) => folder.fold_generated(k, args, span),
+		Token::Include(k) => folder.fold_include(k, span),
+		Token::Translated(id, args) => folder.fold_translated(id, args, span)
+	}
+}
+
+///Merged strings get a span that covers both of them.
+fn push_merged(tokens: &mut Vec<Spanned<Token>>, token: Spanned<Token>) {
+	let Spanned { node, span } = token;
+
+	if let Token::String(s) = node {
+		if s.is_empty() {
+			return;
+		}
+
+		if let Some(&mut Spanned { node: Token::String(ref mut last), span: ref mut last_span }) = tokens.last_mut() {
+			last.push_str(&s);
+			last_span.end = span.end;
+			return;
+		}
+
+		tokens.push(Spanned::new(Token::String(s), span));
+	} else {
+		tokens.push(Spanned::new(node, span));
+	}
+}
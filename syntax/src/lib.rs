@@ -0,0 +1,14 @@
+//!The template syntax of `fragments`, shared by the library and its macros.
+//!
+//!This crate contains the token tree, the parser and the serialization of
+//!token trees back to template source. It should be used through
+//!`fragments`, which re-exports the parts that are part of its API.
+
+pub use token::{Token, Filter, Argument, ArgumentValue};
+pub use span::{Span, Spanned};
+
+mod token;
+mod span;
+pub mod parser;
+pub mod spec;
+pub mod source;
@@ -0,0 +1,622 @@
+use super::{Token, Filter, Argument, ArgumentValue, Span, Spanned};
+use super::spec::FormatSpec;
+
+use std::iter::{Iterator, Peekable};
+use std::fmt;
+
+#[derive(PartialEq)]
+enum LexToken {
+	Begin,
+	End,
+	Colon,
+	Questionmark,
+	Exclamation,
+	Plus,
+	Slash,
+	Quote,
+	Pipe,
+	Character(char)
+}
+
+impl LexToken {
+	fn push_to_buf(&self, buf: &mut String) {
+		match *self {
+			LexToken::Begin => {
+				buf.push('[');
+				buf.push('[');
+			},
+			LexToken::End => {
+				buf.push(']');
+				buf.push(']');
+			},
+			LexToken::Colon => buf.push(':'),
+			LexToken::Questionmark => buf.push('?'),
+			LexToken::Exclamation => buf.push('!'),
+			LexToken::Plus => buf.push('+'),
+			LexToken::Slash => buf.push('/'),
+			LexToken::Quote => buf.push('"'),
+			LexToken::Pipe => buf.push('|'),
+			LexToken::Character(c) => buf.push(c)
+		}
+	}
+}
+
+impl fmt::Display for LexToken {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LexToken::Begin => {
+				'['.fmt(f).and_then(|_| '['.fmt(f))
+			},
+			LexToken::End => ']'.fmt(f).and_then(|_| ']'.fmt(f)),
+			LexToken::Colon => ':'.fmt(f),
+			LexToken::Questionmark => '?'.fmt(f),
+			LexToken::Exclamation => '!'.fmt(f),
+			LexToken::Plus => '+'.fmt(f),
+			LexToken::Slash => '/'.fmt(f),
+			LexToken::Quote => '"'.fmt(f),
+			LexToken::Pipe => '|'.fmt(f),
+			LexToken::Character(c) => c.fmt(f)
+		}
+	}
+}
+
+
+struct Parser<I: Iterator<Item=(LexToken, Span)>> {
+	tokens: Peekable<I>,
+	position: usize
+}
+
+impl<I: Iterator<Item=(LexToken, Span)>> Parser<I> {
+	#[inline]
+	fn eat(&mut self, expected: LexToken) -> bool {
+		let eaten = self.peek() == Some(&expected);
+
+		if eaten {
+			self.next();
+		}
+
+		eaten
+	}
+
+	fn eat_while<F: Fn(&LexToken) -> bool>(&mut self, is_edible: F) {
+		loop {
+			let eaten = match self.peek() {
+				Some(t) => is_edible(t),
+				_ => false
+			};
+
+			if eaten {
+				self.next();
+			} else {
+				break;
+			}
+		}
+	}
+
+	#[inline]
+	fn peek(&mut self) -> Option<&LexToken> {
+		self.tokens.peek().map(|(t, _)| t)
+	}
+
+	///The byte offset where the next token starts, or the end of the source.
+	#[inline]
+	fn offset(&mut self) -> usize {
+		match self.tokens.peek() {
+			Some(&(_, span)) => span.start,
+			None => self.position
+		}
+	}
+
+	///The byte offset where the last token ended.
+	#[inline]
+	fn end_offset(&self) -> usize {
+		self.position
+	}
+}
+
+impl<I: Iterator<Item=(LexToken, Span)>> Iterator for Parser<I> {
+	type Item = LexToken;
+
+	#[inline]
+	fn next(&mut self) -> Option<LexToken> {
+		match self.tokens.next() {
+			Some((t, span)) => {
+				self.position = span.end;
+				Some(t)
+			},
+			None => None
+		}
+	}
+}
+
+pub fn parse<T: Iterator<Item=Result<char, String>>>(chars: T) -> Result<Vec<Spanned<Token>>, Spanned<String>> {
+	let tokens = lex(chars)?;
+	parse_block(&mut Parser{
+		tokens: tokens.into_iter().by_ref().peekable(),
+		position: 0
+	})
+}
+
+fn lex<T: Iterator<Item=Result<char, String>>>(chars: T) -> Result<Vec<(LexToken, Span)>, Spanned<String>> {
+	let mut chars = chars.peekable();
+	let mut tokens = Vec::new();
+	let mut offset = 0;
+
+	loop {
+		let start = offset;
+
+		let token = match chars.next() {
+			Some(Ok(c)) => {
+				offset += c.len_utf8();
+
+				match c {
+					'[' => match chars.peek() {
+						Some(&Ok('[')) => {
+							chars.next();
+							offset += 1;
+							LexToken::Begin
+						},
+						_ => LexToken::Character('[')
+					},
+					']' => match chars.peek() {
+						Some(&Ok(']')) => {
+							chars.next();
+							offset += 1;
+							LexToken::End
+						},
+						_ => LexToken::Character(']')
+					},
+					':' => LexToken::Colon,
+					'?' => LexToken::Questionmark,
+					'!' => LexToken::Exclamation,
+					'+' => LexToken::Plus,
+					'/' => LexToken::Slash,
+					'"' => LexToken::Quote,
+					'|' => LexToken::Pipe,
+					'\\' => match chars.next() {
+						Some(Ok(c)) => {
+							offset += c.len_utf8();
+							LexToken::Character(c)
+						},
+						Some(Err(e)) => return Err(Spanned::new(e, Span::new(start, offset))),
+						None => break
+					},
+					c => LexToken::Character(c)
+				}
+			},
+			Some(Err(e)) => return Err(Spanned::new(e, Span::new(start, offset))),
+			None => break
+		};
+
+		tokens.push((token, Span::new(start, offset)));
+	}
+
+	Ok(tokens)
+}
+
+fn parse_block<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Vec<Spanned<Token>>, Spanned<String>> {
+	let mut result = Vec::new();
+	let mut string = String::new();
+	let mut string_start = 0;
+
+	loop {
+		let start = tokens.offset();
+
+		match tokens.next() {
+			Some(LexToken::Begin) => match tokens.next() {
+				Some(LexToken::Colon) => {
+					push_string(&mut result, &mut string, string_start, start);
+					let token = parse_placeholder(tokens)?;
+					result.push(Spanned::new(token, Span::new(start, tokens.end_offset())));
+				},
+				Some(LexToken::Questionmark) => {
+					push_string(&mut result, &mut string, string_start, start);
+					let token = parse_conditional(tokens)?;
+					result.push(Spanned::new(token, Span::new(start, tokens.end_offset())));
+				},
+				Some(LexToken::Plus) => {
+					push_string(&mut result, &mut string, string_start, start);
+					let token = parse_generator(tokens)?;
+					result.push(Spanned::new(token, Span::new(start, tokens.end_offset())));
+				},
+				Some(LexToken::Character('>')) => {
+					push_string(&mut result, &mut string, string_start, start);
+					let token = parse_include(tokens)?;
+					result.push(Spanned::new(token, Span::new(start, tokens.end_offset())));
+				},
+				Some(LexToken::Character('~')) => {
+					push_string(&mut result, &mut string, string_start, start);
+					let token = parse_translation(tokens)?;
+					result.push(Spanned::new(token, Span::new(start, tokens.end_offset())));
+				},
+				Some(LexToken::Slash) => {
+					push_string(&mut result, &mut string, string_start, start);
+					parse_block_end(tokens);
+					break
+				},
+				Some(t) => {
+					let message = format!("parse error: unknown token type: '{}'", t);
+					return Err(Spanned::new(message, Span::new(start, tokens.end_offset())))
+				},
+				None => {
+					if string.is_empty() {
+						string_start = start;
+					}
+
+					LexToken::Begin.push_to_buf(&mut string)
+				}
+			},
+			Some(t) => {
+				if string.is_empty() {
+					string_start = start;
+				}
+
+				t.push_to_buf(&mut string)
+			},
+			None => break
+		}
+	}
+
+	let end = tokens.end_offset();
+	push_string(&mut result, &mut string, string_start, end);
+
+	Ok(result)
+}
+
+fn push_string(result: &mut Vec<Spanned<Token>>, string: &mut String, start: usize, end: usize) {
+	if !string.is_empty() {
+		let s = std::mem::take(string);
+		result.push(Spanned::new(Token::String(s), Span::new(start, end)));
+	}
+}
+
+fn parse_placeholder<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Token, Spanned<String>> {
+	let mut label = String::new();
+	let mut filters = Vec::new();
+
+	loop {
+		match tokens.next() {
+			Some(LexToken::Pipe) => break,
+			Some(LexToken::Colon) => {
+				let spec = parse_format_spec(tokens);
+				check_format_spec(&spec)?;
+				filters.push(Filter::new("format".to_owned(), vec![spec.node]));
+
+				if !tokens.eat(LexToken::Pipe) {
+					tokens.eat(LexToken::End);
+					return Ok(Token::Filtered(label, filters));
+				}
+
+				break;
+			},
+			Some(LexToken::End) | None => return Ok(Token::Placeholder(label)),
+			Some(t) => t.push_to_buf(&mut label)
+		}
+	}
+
+	loop {
+		let start = tokens.offset();
+		let (words, last) = parse_filter_words(tokens);
+
+		if let [ref name, ref spec] = words[..] {
+			if name.node == "format" {
+				check_format_spec(spec)?;
+			}
+		}
+
+		let mut words = words.into_iter().map(|word| word.node);
+		let filter = Filter::new(words.next().unwrap_or_default(), words.collect());
+
+		if let Err(message) = check_filter(&filter) {
+			return Err(Spanned::new(message, Span::new(start, tokens.end_offset())));
+		}
+
+		filters.push(filter);
+
+		if last {
+			return Ok(Token::Filtered(label, filters));
+		}
+	}
+}
+
+///Parse the format spec after the label of a placeholder, like `>8` in
+///`[[:id:>8]]`. It ends where the filters or the placeholder end.
+fn parse_format_spec<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Spanned<String> {
+	let start = tokens.offset();
+	let mut spec = String::new();
+
+	loop {
+		match tokens.peek() {
+			Some(&LexToken::End) | Some(&LexToken::Pipe) | None => return Spanned::new(spec, Span::new(start, tokens.end_offset().max(start))),
+			_ => {}
+		}
+
+		if let Some(t) = tokens.next() {
+			t.push_to_buf(&mut spec);
+		}
+	}
+}
+
+///Parse the whitespace separated, and possibly quoted, words of a filter.
+///The result is `true` if it's the last filter.
+fn parse_filter_words<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> (Vec<Spanned<String>>, bool) {
+	let mut words = Vec::new();
+
+	loop {
+		tokens.eat_while(|t| matches!(*t, LexToken::Character(c) if c.is_whitespace()));
+
+		if tokens.eat(LexToken::Pipe) {
+			return (words, false);
+		}
+
+		if tokens.eat(LexToken::End) || tokens.peek().is_none() {
+			return (words, true);
+		}
+
+		let quoted = tokens.eat(LexToken::Quote);
+		let start = tokens.offset();
+		let mut word = String::new();
+
+		if quoted {
+			for t in tokens.by_ref().take_while(|t| *t != LexToken::Quote) {
+				t.push_to_buf(&mut word);
+			}
+		} else {
+			loop {
+				match tokens.peek() {
+					Some(&LexToken::End) | Some(&LexToken::Pipe) | None => break,
+					Some(&LexToken::Character(c)) if c.is_whitespace() => break,
+					_ => {}
+				}
+
+				if let Some(t) = tokens.next() {
+					t.push_to_buf(&mut word);
+				}
+			}
+		}
+
+		words.push(Spanned::new(word, Span::new(start, tokens.end_offset().max(start))));
+	}
+}
+
+///Filters are checked when they are parsed, to catch misspelled names early.
+fn check_filter(filter: &Filter) -> Result<(), String> {
+	const ESCAPERS: &[&str] = &["none", "html", "json", "url", "csv", "shell", "xml"];
+
+	match &*filter.name {
+		"escape" => match filter.args.first() {
+			Some(name) if filter.args.len() == 1 && ESCAPERS.contains(&&**name) => Ok(()),
+			_ => Err(format!("parse error: 'escape' expects one of {}", ESCAPERS.join(", ")))
+		},
+		"format" => match filter.args.first() {
+			//the spec itself is checked with `check_format_spec`, where its position is known
+			Some(_) if filter.args.len() == 1 => Ok(()),
+			_ => Err("parse error: 'format' expects a format spec".to_owned())
+		},
+		"date" => match filter.args.len() {
+			0 => Ok(()),
+			1 => check_date_pattern(&filter.args[0]).map_err(|e| format!("parse error: {}", e)),
+			_ => Err("parse error: 'date' expects at most one pattern".to_owned())
+		},
+		"relative" if filter.args.is_empty() => Ok(()),
+		"relative" => Err("parse error: 'relative' doesn't take any arguments".to_owned()),
+		"" => Err("parse error: missing filter name".to_owned()),
+		name => Err(format!("parse error: unknown filter: '{}'", name))
+	}
+}
+
+///Check a format spec, and point the error at the part of it that is wrong.
+fn check_format_spec(spec: &Spanned<String>) -> Result<(), Spanned<String>> {
+	match FormatSpec::parse(spec) {
+		Ok(_) => Ok(()),
+		Err(e) => Err(Spanned::new(
+			format!("parse error: invalid format spec '{}': {}", spec.node, e.node),
+			Span::new(spec.span.start + e.span.start, spec.span.start + e.span.end)
+		))
+	}
+}
+
+///Check that a pattern for the `date` filter only contains directives
+///that `DateTime::format` in `fragments` supports.
+fn check_date_pattern(pattern: &str) -> Result<(), String> {
+	let mut chars = pattern.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '%' {
+			continue;
+		}
+
+		let mut directive = chars.next();
+		if directive == Some('-') || directive == Some(':') {
+			directive = chars.next();
+		}
+
+		match directive {
+			Some(c) if "YCymdejHIMSpaAbhBuwszZFTRDnt%".contains(c) => {},
+			Some(c) => return Err(format!("unknown date directive '%{}'", c)),
+			None => return Err("incomplete date directive at the end".to_owned())
+		}
+	}
+
+	Ok(())
+}
+
+fn parse_include<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Token, Spanned<String>> {
+	let mut name = String::new();
+
+	for t in tokens.by_ref().take_while(|t| *t != LexToken::End) {
+		t.push_to_buf(&mut name);
+	}
+
+	Ok(Token::Include(name))
+}
+
+///Parse a translation token, like `[[~greeting name=:name title="Dr."]]`.
+///Each argument is a name, followed by `=` and either `:` and a label or a
+///literal text.
+fn parse_translation<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Token, Spanned<String>> {
+	let start = tokens.offset();
+	let id = parse_word(tokens);
+	let mut args = Vec::new();
+
+	if id.is_empty() {
+		return Err(Spanned::new("parse error: missing message id".to_owned(), Span::new(start, tokens.end_offset())));
+	}
+
+	loop {
+		tokens.eat_while(|t| matches!(*t, LexToken::Character(c) if c.is_whitespace()));
+
+		if tokens.eat(LexToken::End) || tokens.peek().is_none() {
+			return Ok(Token::Translated(id, args));
+		}
+
+		let start = tokens.offset();
+		let mut name = String::new();
+
+		let complete = loop {
+			match tokens.peek() {
+				Some(&LexToken::Character('=')) => break true,
+				Some(&LexToken::End) | None => break false,
+				Some(&LexToken::Character(c)) if c.is_whitespace() => break false,
+				_ => {}
+			}
+
+			if let Some(t) = tokens.next() {
+				t.push_to_buf(&mut name);
+			}
+		};
+
+		if !complete {
+			let message = format!("parse error: expected '=' after the argument '{}'", name);
+			return Err(Spanned::new(message, Span::new(start, tokens.end_offset())));
+		}
+
+		tokens.next();
+
+		if name.is_empty() {
+			return Err(Spanned::new("parse error: missing argument name".to_owned(), Span::new(start, tokens.end_offset())));
+		}
+
+		let value = if tokens.eat(LexToken::Colon) {
+			ArgumentValue::Label(parse_word(tokens))
+		} else {
+			ArgumentValue::Text(parse_word(tokens))
+		};
+
+		args.push(Argument::new(name, value));
+	}
+}
+
+///Parse a word that ends with whitespace or the end of the tag, or a quoted word.
+fn parse_word<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> String {
+	let mut word = String::new();
+
+	if tokens.eat(LexToken::Quote) {
+		for t in tokens.by_ref().take_while(|t| *t != LexToken::Quote) {
+			t.push_to_buf(&mut word);
+		}
+
+		return word;
+	}
+
+	loop {
+		match tokens.peek() {
+			Some(&LexToken::End) | None => return word,
+			Some(&LexToken::Character(c)) if c.is_whitespace() => return word,
+			_ => {}
+		}
+
+		if let Some(t) = tokens.next() {
+			t.push_to_buf(&mut word);
+		}
+	}
+}
+
+fn parse_conditional<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Token, Spanned<String>> {
+	let negative = tokens.eat(LexToken::Exclamation);
+	let content_cond = tokens.eat(LexToken::Colon);
+	let mut label = String::new();
+
+	for t in tokens.by_ref().take_while(|t| *t != LexToken::End) {
+		t.push_to_buf(&mut label);
+	}
+
+	let content = parse_block(tokens)?;
+
+	if content_cond {
+		Ok(Token::ContentConditional(label, !negative, content))
+	} else {
+		Ok(Token::Conditional(label, !negative, content))
+	}
+}
+
+fn parse_generator<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) -> Result<Token, Spanned<String>> {
+	let mut label = String::new();
+	let mut args = Vec::new();
+
+	if tokens.eat(LexToken::Quote) {
+		for t in tokens.by_ref().take_while(|t| *t != LexToken::Quote) {
+			t.push_to_buf(&mut label);
+		}
+	} else {
+		loop {
+			match tokens.next() {
+				Some(LexToken::End) => return Ok(Token::Generated(label, Vec::new())),
+				Some(LexToken::Character(c)) if c.is_whitespace() => break,
+				Some(t) => t.push_to_buf(&mut label),
+				None => break
+			}
+		}
+	}
+
+	'arg_list: loop {
+		let mut new_arg = String::new();
+
+		tokens.eat_while(|t| matches!(*t, LexToken::Character(c) if c.is_whitespace()));
+
+		if tokens.eat(LexToken::End) {
+			break 'arg_list;
+		}
+
+		let start = tokens.offset();
+		let end;
+
+		if tokens.eat(LexToken::Quote) {
+			for t in tokens.by_ref().take_while(|t| *t != LexToken::Quote) {
+				t.push_to_buf(&mut new_arg);
+			}
+
+			end = tokens.end_offset();
+		} else {
+			'arg: loop {
+				let offset = tokens.offset();
+
+				match tokens.next() {
+					Some(LexToken::End) => {
+						args.push(Spanned::new(new_arg, Span::new(start, offset)));
+						break 'arg_list;
+					},
+					Some(LexToken::Character(c)) if c.is_whitespace() => {
+						end = offset;
+						break 'arg;
+					},
+					Some(t) => t.push_to_buf(&mut new_arg),
+					None => {
+						if !new_arg.is_empty() {
+							args.push(Spanned::new(new_arg, Span::new(start, offset)));
+						}
+						break 'arg_list;
+					}
+				}
+			}
+		}
+
+		args.push(Spanned::new(new_arg, Span::new(start, end)));
+	}
+
+	Ok(Token::Generated(label, args))
+}
+
+fn parse_block_end<I: Iterator<Item=(LexToken, Span)>>(tokens: &mut Parser<I>) {
+	tokens.by_ref().all(|t| t != LexToken::End);
+}
@@ -0,0 +1,173 @@
+use super::{Token, ArgumentValue, Spanned};
+
+use std::fmt;
+
+impl fmt::Display for Token {
+	///Writes the token as template source, which can be parsed again.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Token::String(s) => write_string(s, f),
+
+			Token::Placeholder(k) => {
+				f.write_str("[[:")?;
+				write_label(k, f)?;
+				f.write_str("]]")
+			},
+
+			Token::Filtered(k, filters) => {
+				f.write_str("[[:")?;
+				write_label(k, f)?;
+
+				let mut filters = filters.iter().peekable();
+				if let Some(filter) = filters.peek() {
+					if filter.name == "format" && filter.args.len() == 1 {
+						f.write_str(":")?;
+						write_label(&filter.args[0], f)?;
+						filters.next();
+					}
+				}
+
+				for filter in filters {
+					f.write_str("|")?;
+					write_word(&filter.name, f)?;
+
+					for arg in filter.args.iter() {
+						f.write_str(" ")?;
+						write_word(arg, f)?;
+					}
+				}
+
+				f.write_str("]]")
+			},
+
+			&Token::Conditional(ref k, expected, ref tokens) => write_block(k, expected, false, tokens, f),
+
+			&Token::ContentConditional(ref k, expected, ref tokens) => write_block(k, expected, true, tokens, f),
+
+			Token::Include(k) => {
+				f.write_str("[[>")?;
+				write_label(k, f)?;
+				f.write_str("]]")
+			},
+
+			Token::Translated(id, args) => {
+				f.write_str("[[~")?;
+				write_word(id, f)?;
+
+				for arg in args.iter() {
+					f.write_str(" ")?;
+					write_word(&arg.name, f)?;
+					f.write_str("=")?;
+
+					match arg.value {
+						ArgumentValue::Label(ref label) => {
+							f.write_str(":")?;
+							write_word(label, f)?;
+						},
+						ArgumentValue::Text(ref text) => write_word(text, f)?
+					}
+				}
+
+				f.write_str("]]")
+			},
+
+			Token::Generated(k, args) => {
+				f.write_str("[[+")?;
+				write_word(k, f)?;
+
+				for arg in args.iter() {
+					f.write_str(" ")?;
+					write_word(arg, f)?;
+				}
+
+				f.write_str("]]")
+			}
+		}
+	}
+}
+
+///Write a token tree as template source.
+pub fn write_tokens(tokens: &[Spanned<Token>], f: &mut fmt::Formatter) -> fmt::Result {
+	for token in tokens.iter() {
+		fmt::Display::fmt(token, f)?;
+	}
+
+	Ok(())
+}
+
+fn write_block(label: &str, expected: bool, content: bool, tokens: &[Spanned<Token>], f: &mut fmt::Formatter) -> fmt::Result {
+	f.write_str("[[")?;
+	f.write_str(match (expected, content) {
+		(true, false) => "?",
+		(false, false) => "?!",
+		(true, true) => "?:",
+		(false, true) => "?!:"
+	})?;
+
+	if label.starts_with('!') {
+		f.write_str("\\")?;
+	}
+
+	write_label(label, f)?;
+	f.write_str("]]")?;
+	write_tokens(tokens, f)?;
+	f.write_str("[[/]]")
+}
+
+///Plain text only needs `[` to be escaped where it could become the start of a tag.
+fn write_string(s: &str, f: &mut fmt::Formatter) -> fmt::Result {
+	let mut chars = s.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => f.write_str("\\\\")?,
+			'[' => match chars.peek() {
+				Some(&'[') | None => f.write_str("\\[")?,
+				_ => f.write_str("[")?
+			},
+			c => fmt::Write::write_char(f, c)?
+		}
+	}
+
+	Ok(())
+}
+
+///Labels end at `]]`, format specs start at `:` and filters start at `|`,
+///so brackets, colons and pipes are escaped.
+fn write_label(label: &str, f: &mut fmt::Formatter) -> fmt::Result {
+	for c in label.chars() {
+		match c {
+			'\\' | '[' | ']' | ':' | '|' => {
+				f.write_str("\\")?;
+				fmt::Write::write_char(f, c)?;
+			},
+			c => fmt::Write::write_char(f, c)?
+		}
+	}
+
+	Ok(())
+}
+
+///Generator labels and arguments are separated by whitespace, so they are
+///quoted if they contain whitespace, are empty or would otherwise start with a quote.
+fn write_word(word: &str, f: &mut fmt::Formatter) -> fmt::Result {
+	let quoted = word.is_empty() || word.starts_with('"') || word.chars().any(|c| c.is_whitespace());
+
+	if !quoted {
+		return write_label(word, f);
+	}
+
+	f.write_str("\"")?;
+
+	for c in word.chars() {
+		match c {
+			'\\' | '"' => {
+				f.write_str("\\")?;
+				fmt::Write::write_char(f, c)?;
+			},
+			c => fmt::Write::write_char(f, c)?
+		}
+	}
+
+	f.write_str("\"")
+}
@@ -0,0 +1,111 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+///A byte range in the source of a template.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Span {
+	///The byte offset of the first byte in the range.
+	pub start: usize,
+	///The byte offset just after the last byte in the range.
+	pub end: usize
+}
+
+impl Span {
+	///Create a new `Span` from `start` to, but not including, `end`.
+	#[inline]
+	pub fn new(start: usize, end: usize) -> Span {
+		Span {
+			start,
+			end
+		}
+	}
+
+	///The length of the range, in bytes.
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	///Check if the range doesn't cover any bytes.
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.end <= self.start
+	}
+}
+
+///A value, together with where it's located in the template source.
+///
+///The span is ignored when spanned values are compared, so two `Spanned`
+///values are equal if their values are equal, no matter where they came
+///from. A `Spanned` value can also be compared directly to an unspanned value.
+#[derive(Clone, Debug)]
+pub struct Spanned<T> {
+	///The value.
+	pub node: T,
+	///The location of the value in the template source.
+	pub span: Span
+}
+
+impl<T> Spanned<T> {
+	///Create a new `Spanned` value.
+	#[inline]
+	pub fn new(node: T, span: Span) -> Spanned<T> {
+		Spanned {
+			node,
+			span
+		}
+	}
+}
+
+impl<T> From<T> for Spanned<T> {
+	///Creates a `Spanned` value with an empty span at the beginning of the source.
+	#[inline]
+	fn from(node: T) -> Spanned<T> {
+		Spanned::new(node, Span::default())
+	}
+}
+
+impl<T> Deref for Spanned<T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		&self.node
+	}
+}
+
+impl<T> DerefMut for Spanned<T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut T {
+		&mut self.node
+	}
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+	#[inline]
+	fn eq(&self, other: &Spanned<T>) -> bool {
+		self.node == other.node
+	}
+}
+
+impl<T: PartialEq> PartialEq<T> for Spanned<T> {
+	#[inline]
+	fn eq(&self, other: &T) -> bool {
+		self.node == *other
+	}
+}
+
+///Lets lists of spanned strings be joined and concatenated like lists of strings.
+impl Borrow<str> for Spanned<String> {
+	#[inline]
+	fn borrow(&self) -> &str {
+		&self.node
+	}
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(&self.node, f)
+	}
+}
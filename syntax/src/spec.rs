@@ -0,0 +1,206 @@
+use super::{Span, Spanned};
+
+use std::fmt;
+
+///How formatted content is aligned within its width.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+	Left,
+	Center,
+	Right
+}
+
+///How a formatted number is written.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Notation {
+	///Without exponent, like `1200.5`.
+	Fixed,
+	///With a base 10 exponent, like `1.2005e3`.
+	Exponent,
+	///With a base 2 exponent, like `1.172364p10`.
+	BinaryExponent,
+	///With the decimal mark and group separators of the locale, like `1,200.5`.
+	Number,
+	///As an amount in the currency of the locale, like `$1,200.50`.
+	Currency,
+	///Multiplied by 100, as a percentage in the locale, like `12%`.
+	Percent
+}
+
+///A format spec from a placeholder, like `[[:price:.2]]`.
+///
+///The syntax is `[[fill]align][sign][0][width][.precision][notation[precision]]`:
+///
+/// * `align` is `<`, `^` or `>`, for left, center or right alignment, and
+///   `fill` is any character that is used for padding, instead of spaces.
+/// * `sign` is `+`, to always write the sign of numbers, or `-`, which is the default.
+/// * `0` pads numbers with zeros between the sign and the digits.
+/// * `width` is the minimum number of characters.
+/// * `precision` is the number of decimals in numbers, or the maximum
+///   number of characters in other content.
+/// * `notation` is `f` for numbers without exponent, `e` for a base 10
+///   exponent and `p` for a base 2 exponent. It's `n` for numbers, `c` for
+///   currencies and `%` for percentages in the locale of the template. It
+///   can be followed by the precision, like `e3`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FormatSpec {
+	pub fill: char,
+	pub align: Option<Align>,
+	pub sign: bool,
+	pub zero: bool,
+	pub width: Option<usize>,
+	pub precision: Option<usize>,
+	pub notation: Option<Notation>
+}
+
+impl FormatSpec {
+	///Parse a format spec, like `>8` or `+.2e`. The span of an error is
+	///counted in bytes from the start of `spec`.
+	pub fn parse(spec: &str) -> Result<FormatSpec, Spanned<String>> {
+		let chars: Vec<char> = spec.chars().collect();
+		let mut position = 0;
+		let mut result = FormatSpec {
+			fill: ' ',
+			align: None,
+			sign: false,
+			zero: false,
+			width: None,
+			precision: None,
+			notation: None
+		};
+
+		if let Some(align) = chars.get(1).and_then(|&c| to_align(c)) {
+			result.fill = chars[0];
+			result.align = Some(align);
+			position = 2;
+		} else if let Some(align) = chars.first().and_then(|&c| to_align(c)) {
+			result.align = Some(align);
+			position = 1;
+		}
+
+		match chars.get(position) {
+			Some(&'+') => {
+				result.sign = true;
+				position += 1;
+			},
+			Some(&'-') => position += 1,
+			_ => {}
+		}
+
+		if chars.get(position) == Some(&'0') {
+			result.zero = true;
+			position += 1;
+		}
+
+		result.width = parse_number(&chars, &mut position)?;
+
+		if chars.get(position) == Some(&'.') {
+			position += 1;
+			match parse_number(&chars, &mut position)? {
+				Some(precision) => result.precision = Some(precision),
+				None => return Err(error(&chars, position - 1, position, "expected a precision after '.'".to_owned()))
+			}
+		}
+
+		if let Some(&c) = chars.get(position) {
+			result.notation = match c {
+				'f' => Some(Notation::Fixed),
+				'e' => Some(Notation::Exponent),
+				'p' => Some(Notation::BinaryExponent),
+				'n' => Some(Notation::Number),
+				'c' => Some(Notation::Currency),
+				'%' => Some(Notation::Percent),
+				c => return Err(error(&chars, position, position + 1, format!("unexpected '{}'", c)))
+			};
+			position += 1;
+
+			let start = position;
+			if let Some(precision) = parse_number(&chars, &mut position)? {
+				if result.precision.is_some() {
+					return Err(error(&chars, start, position, "the precision is given twice".to_owned()));
+				}
+
+				result.precision = Some(precision);
+			}
+		}
+
+		match chars.get(position) {
+			Some(c) => Err(error(&chars, position, position + 1, format!("unexpected '{}'", c))),
+			None => Ok(result)
+		}
+	}
+
+	///Pad `s` to the width of the spec. Numbers are aligned to the right,
+	///and everything else to the left, unless the spec says otherwise.
+	pub fn pad(&self, s: &str, number: bool, f: &mut dyn fmt::Write) -> fmt::Result {
+		let len = s.chars().count();
+		let width = self.width.unwrap_or(0);
+
+		if len >= width {
+			return f.write_str(s);
+		}
+
+		let padding = width - len;
+
+		if number && self.zero && self.align.is_none() {
+			let (sign, digits) = match s.chars().next() {
+				Some(c) if c == '+' || c == '-' => s.split_at(1),
+				_ => ("", s)
+			};
+
+			f.write_str(sign)?;
+			write_fill(f, '0', padding)?;
+			return f.write_str(digits);
+		}
+
+		let align = self.align.unwrap_or(if number { Align::Right } else { Align::Left });
+		let (before, after) = match align {
+			Align::Left => (0, padding),
+			Align::Center => (padding / 2, padding - padding / 2),
+			Align::Right => (padding, 0)
+		};
+
+		write_fill(f, self.fill, before)?;
+		f.write_str(s)?;
+		write_fill(f, self.fill, after)
+	}
+}
+
+fn to_align(c: char) -> Option<Align> {
+	match c {
+		'<' => Some(Align::Left),
+		'^' => Some(Align::Center),
+		'>' => Some(Align::Right),
+		_ => None
+	}
+}
+
+fn parse_number(chars: &[char], position: &mut usize) -> Result<Option<usize>, Spanned<String>> {
+	let start = *position;
+	while chars.get(*position).is_some_and(|c| c.is_ascii_digit()) {
+		*position += 1;
+	}
+
+	if *position == start {
+		return Ok(None);
+	}
+
+	match chars[start..*position].iter().collect::<String>().parse() {
+		Ok(number) => Ok(Some(number)),
+		Err(_) => Err(error(chars, start, *position, "the number is too large".to_owned()))
+	}
+}
+
+///An error about the characters from `start` to `end`, with a span in bytes.
+fn error(chars: &[char], start: usize, end: usize, message: String) -> Spanned<String> {
+	let offset = |index: usize| chars[..index].iter().map(|c| c.len_utf8()).sum();
+	Spanned::new(message, Span::new(offset(start), offset(end)))
+}
+
+fn write_fill(f: &mut dyn fmt::Write, fill: char, count: usize) -> fmt::Result {
+	for _ in 0..count {
+		f.write_char(fill)?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,65 @@
+use super::Spanned;
+
+///Internal representation of template parts.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Token {
+	String(String),
+	Placeholder(String),
+	Filtered(String, Vec<Filter>),
+	Conditional(String, bool, Vec<Spanned<Token>>),
+	ContentConditional(String, bool, Vec<Spanned<Token>>),
+	Generated(String, Vec<Spanned<String>>),
+	Include(String),
+	Translated(String, Vec<Argument>)
+}
+
+///A filter for the content of a placeholder, like `escape url` in `[[:query|escape url]]`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Filter {
+	pub name: String,
+	pub args: Vec<String>
+}
+
+impl Filter {
+	///Create a new filter with the arguments `args`.
+	pub fn new(name: String, args: Vec<String>) -> Filter {
+		Filter {
+			name,
+			args
+		}
+	}
+}
+
+///An argument of a translation token, like `name=:name` in `[[~greeting name=:name]]`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Argument {
+	pub name: String,
+	pub value: ArgumentValue
+}
+
+impl Argument {
+	///Create a new argument with the value `value`.
+	pub fn new(name: String, value: ArgumentValue) -> Argument {
+		Argument {
+			name,
+			value
+		}
+	}
+
+	///The label of the placeholder that provides the value, if there is one.
+	pub fn label(&self) -> Option<&str> {
+		match self.value {
+			ArgumentValue::Label(ref label) => Some(label),
+			ArgumentValue::Text(_) => None
+		}
+	}
+}
+
+///The value of a translation argument.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ArgumentValue {
+	///The content of a placeholder, like `:name`.
+	Label(String),
+	///A literal text, like `"Anna"`.
+	Text(String)
+}